@@ -5,10 +5,11 @@ use fastgmad::{
 	create::{CreateGmaConfig, CreateGmadOut},
 	error::{FastGmadError, FastGmadErrorKind},
 	extract::{ExtractGmaConfig, ExtractGmadIn},
+	whitelist::Whitelist,
 	workshop::{WorkshopPublishConfig, WorkshopUpdateConfig},
 };
 use std::{
-	ffi::OsStr,
+	ffi::{OsStr, OsString},
 	fs::File,
 	io::{BufReader, BufWriter, Write},
 	path::{Path, PathBuf},
@@ -90,6 +91,15 @@ fn main() {
 			Err(err).unwrap()
 		}
 
+		Err(FastGmadBinError::FastGmadError(FastGmadError {
+			kind: FastGmadErrorKind::Aborted,
+			..
+		})) => {
+			eprintln!();
+			log::warn!("Aborted by user");
+			exit_ctrlc();
+		}
+
 		Err(FastGmadBinError::FastGmadError(err)) => {
 			eprintln!();
 			log::error!("{err}\n");
@@ -103,6 +113,8 @@ fn main() {
 
 			eprintln!("{}", include_str!("usage.txt"));
 		}
+
+		Err(FastGmadBinError::Exit(code)) => std::process::exit(code),
 	}
 }
 
@@ -143,8 +155,12 @@ fn bin() -> Result<(), FastGmadBinError> {
 	} else {
 		match cmd.to_str() {
 			Some("create") => {
-				let (conf, out) = CreateGmaConfig::from_args(std::env::args_os().skip(2))?;
-				create(conf, out, &mut exit)
+				let (conf, folders, out) = CreateGmaConfig::from_args(std::env::args_os().skip(2))?;
+				if folders.len() > 1 {
+					create_many(conf, folders, &mut exit)
+				} else {
+					create(conf, out, &mut exit)
+				}
 			}
 
 			Some("extract") => {
@@ -156,6 +172,10 @@ fn bin() -> Result<(), FastGmadBinError> {
 
 			Some("update") => update(WorkshopUpdateConfig::from_args()?),
 
+			Some("whitelist") => whitelist(std::env::args_os().skip(2)),
+
+			Some("suggest") => suggest(std::env::args_os().skip(2)),
+
 			_ => Err(FastGmadBinError::PrintHelp(None)),
 		}
 	}
@@ -164,27 +184,80 @@ fn bin() -> Result<(), FastGmadBinError> {
 fn create(conf: CreateGmaConfig, out: CreateGmadOut, exit: &mut impl FnMut()) -> Result<(), FastGmadBinError> {
 	match out {
 		CreateGmadOut::File(path) => {
+			// Write to a temporary file next to the output file and only move it into place once the GMA is complete,
+			// so that a failed build doesn't destroy the previous GMA
+			let temp_path = path.with_file_name(format!(
+				".{}.fastgmad-{}.tmp",
+				path.file_name().unwrap_or_default().to_string_lossy(),
+				std::process::id()
+			));
+
+			let mut conf = conf;
+			conf.exclude.push(path.clone());
+			conf.exclude.push(temp_path.clone());
+
 			log::info!("Opening output file...");
-			let mut w = BufWriter::new(File::create(&path).map_err(|error| FastGmadError {
-				kind: FastGmadErrorKind::PathIoError { path, error },
+			let w = File::create(&temp_path).map_err(|error| FastGmadError {
+				kind: FastGmadErrorKind::PathIoError {
+					path: temp_path.clone(),
+					error,
+				},
 				context: Some("opening output file".to_string()),
-			})?);
-			fastgmad::create::seekable_create_gma_with_done_callback(&conf, &mut w, exit)?;
+			})?;
+
+			let res = (|| {
+				let mut w = BufWriter::new(w);
+				fastgmad::create::create_gma_to_file_with_done_callback(&conf, &mut w, &mut || ())?;
+				w.into_inner().map_err(|error| FastGmadError {
+					kind: FastGmadErrorKind::PathIoError {
+						path: temp_path.clone(),
+						error: error.into_error(),
+					},
+					context: Some("flushing output file".to_string()),
+				})?;
+				std::fs::rename(&temp_path, &path).map_err(|error| FastGmadError {
+					kind: FastGmadErrorKind::DoublePathIoError {
+						a: temp_path.clone(),
+						b: path.clone(),
+						error,
+					},
+					context: Some("moving output file into place".to_string()),
+				})
+			})();
+			if res.is_err() {
+				// Don't leave a truncated GMA behind
+				std::fs::remove_file(&temp_path).ok();
+			}
+			res?;
+
+			exit();
 		}
 
 		CreateGmadOut::Stdout => {
 			let mut w = std::io::stdout().lock();
-			if conf.max_io_threads.get() != 1 {
-				log::warn!("Writing to stdout cannot take advantage of multithreading; ignoring -max-io-threads");
-			}
-
 			fastgmad::create::create_gma_with_done_callback(&conf, &mut w, exit)?;
 		}
 	}
 	Ok(())
 }
 
+/// Writes several GMAs back to back to stdout
+fn create_many(conf: CreateGmaConfig, folders: Vec<PathBuf>, exit: &mut impl FnMut()) -> Result<(), FastGmadBinError> {
+	let mut w = std::io::stdout().lock();
+	for folder in folders {
+		log::info!("Creating GMA from {}...", folder.display());
+		let conf = CreateGmaConfig { folder, ..conf.clone() };
+		fastgmad::create::create_gma_with_done_callback(&conf, &mut w, &mut || ())?;
+	}
+	exit();
+	Ok(())
+}
+
 fn extract(conf: ExtractGmaConfig, r#in: ExtractGmadIn, exit: &mut impl FnMut()) -> Result<(), FastGmadBinError> {
+	if conf.multiple {
+		return extract_many(conf, r#in, exit);
+	}
+
 	match r#in {
 		ExtractGmadIn::File(path) => {
 			log::info!("Opening input file...");
@@ -192,7 +265,7 @@ fn extract(conf: ExtractGmaConfig, r#in: ExtractGmadIn, exit: &mut impl FnMut())
 				kind: FastGmadErrorKind::PathIoError { path, error },
 				context: Some("opening input file".to_string()),
 			})?);
-			fastgmad::extract::extract_gma_with_done_callback(&conf, &mut r, exit)?;
+			fastgmad::extract::extract_gma_from_file_with_done_callback(&conf, &mut r, exit)?;
 		}
 
 		ExtractGmadIn::Stdin => {
@@ -203,6 +276,138 @@ fn extract(conf: ExtractGmaConfig, r#in: ExtractGmadIn, exit: &mut impl FnMut())
 	Ok(())
 }
 
+/// Extracts several GMAs that were written back to back
+fn extract_many(conf: ExtractGmaConfig, r#in: ExtractGmadIn, exit: &mut impl FnMut()) -> Result<(), FastGmadBinError> {
+	let outs = match r#in {
+		ExtractGmadIn::File(path) => {
+			log::info!("Opening input file...");
+			let mut r = BufReader::new(File::open(&path).map_err(|error| FastGmadError {
+				kind: FastGmadErrorKind::PathIoError { path, error },
+				context: Some("opening input file".to_string()),
+			})?);
+			fastgmad::extract::extract_gma_stream_with_done_callback(&conf, &mut r, &mut || ())?
+		}
+
+		ExtractGmadIn::Stdin => {
+			let mut r = std::io::stdin().lock();
+			fastgmad::extract::extract_gma_stream_with_done_callback(&conf, &mut r, &mut || ())?
+		}
+	};
+
+	if outs.is_empty() {
+		log::warn!("The input did not contain any GMAs");
+	} else {
+		log::info!("Extracted {} GMAs", outs.len());
+	}
+
+	exit();
+	Ok(())
+}
+
+/// Prints the active whitelist, or explains why each of the given paths is accepted or rejected
+#[cfg(feature = "binary")]
+fn whitelist(mut args: impl Iterator<Item = OsString>) -> Result<(), FastGmadBinError> {
+	let mut files = Vec::new();
+	let mut paths = Vec::new();
+	while let Some(arg) = args.next() {
+		match arg.to_str().ok_or(PrintHelp(Some("Unknown whitelist argument")))? {
+			"-whitelist" => {
+				files.push(
+					args.next()
+						.filter(|whitelist| !whitelist.is_empty())
+						.map(PathBuf::from)
+						.ok_or(PrintHelp(Some("Expected a value after -whitelist")))?,
+				);
+			}
+			path => paths.push(path.replace('\\', "/")),
+		}
+	}
+
+	let whitelist = Whitelist::with_files(&files)?;
+
+	if paths.is_empty() {
+		println!("Whitelist version {}", whitelist.version());
+		for rule in whitelist.rules() {
+			println!("{}\t({})", rule.glob, rule.source);
+		}
+		return Ok(());
+	}
+
+	let mut rejected = false;
+	for path in paths {
+		match whitelist.matching_rule(&path) {
+			Some(rule) => println!("{path}: accepted by {} ({})", rule.glob, rule.source),
+			None => {
+				rejected = true;
+				match whitelist.suggest(&path) {
+					Some(suggestion) => println!("{path}: rejected, no rule matches ({suggestion})"),
+					None => println!("{path}: rejected, no rule matches"),
+				}
+			}
+		}
+	}
+
+	if rejected {
+		return Err(FastGmadBinError::Exit(1));
+	}
+
+	Ok(())
+}
+
+/// Prints the addon type and tags that fit an addon's contents, optionally writing them into its addon.json
+#[cfg(feature = "binary")]
+fn suggest(mut args: impl Iterator<Item = OsString>) -> Result<(), FastGmadBinError> {
+	let mut conf = CreateGmaConfig {
+		warn_invalid: true,
+		..Default::default()
+	};
+	let mut folder = None;
+	let mut write = false;
+	while let Some(arg) = args.next() {
+		match arg.to_str().ok_or(PrintHelp(Some("Unknown suggest argument")))? {
+			"-folder" => {
+				folder = Some(
+					args.next()
+						.filter(|folder| !folder.is_empty())
+						.map(PathBuf::from)
+						.ok_or(PrintHelp(Some("Expected a value after -folder")))?,
+				);
+			}
+			"-profile" => {
+				conf.profile = Some(
+					args.next()
+						.filter(|profile| !profile.is_empty())
+						.and_then(|profile| profile.into_string().ok())
+						.ok_or(PrintHelp(Some("Expected a value after -profile")))?,
+				);
+			}
+			"-write" => write = true,
+			_ => return Err(PrintHelp(Some("Unknown suggest argument")).into()),
+		}
+	}
+	conf.folder = folder.ok_or(PrintHelp(Some("Please provide a folder to suggest a type and tags for")))?;
+
+	let suggestion = fastgmad::create::suggest_addon(&conf)?;
+
+	match suggestion.addon_type() {
+		Some(addon_type) => println!("type: {addon_type}"),
+		None => println!("type: unknown, nothing in the addon suggests a type"),
+	}
+	for other in suggestion.types.iter().skip(1) {
+		println!("also fits: {other}");
+	}
+	for tag in &suggestion.tags {
+		println!("tag: {tag}");
+	}
+
+	if write {
+		fastgmad::create::write_suggestion(&conf, &suggestion)?;
+		log::info!("Wrote the type and tags to {}", conf.folder.join("addon.json").display());
+	}
+
+	Ok(())
+}
+
 #[cfg(any(feature = "binary", feature = "workshop"))]
 fn publish(conf: WorkshopPublishConfig) -> Result<(), FastGmadBinError> {
 	// TODO allow both creation+publishing in a single command
@@ -227,6 +432,9 @@ fn update(conf: WorkshopUpdateConfig) -> Result<(), FastGmadBinError> {
 enum FastGmadBinError {
 	FastGmadError(FastGmadError),
 	PrintHelp(Option<&'static str>),
+
+	/// Exit with this code, the command has already printed why
+	Exit(i32),
 }
 impl From<FastGmadError> for FastGmadBinError {
 	fn from(e: FastGmadError) -> Self {
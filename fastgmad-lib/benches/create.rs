@@ -0,0 +1,76 @@
+//! Creation benchmarks comparing copying entries into a file inside the kernel with copying them through userspace
+//!
+//! cargo bench --package fastgmad --bench create
+
+use fastgmad::create::CreateGmaConfig;
+use std::{
+	fs::File,
+	io::BufWriter,
+	num::NonZeroUsize,
+	path::{Path, PathBuf},
+	time::{Duration, Instant},
+};
+
+const ITERATIONS: u32 = 5;
+
+fn create_addon(dir: &Path, files: usize, file_size: usize) -> PathBuf {
+	let addon_dir = dir.join(format!("addon-{files}x{file_size}"));
+	std::fs::create_dir_all(&addon_dir).unwrap();
+	std::fs::write(addon_dir.join("addon.json"), br#"{"title": "Benchmark"}"#).unwrap();
+
+	let contents = vec![b'-'; file_size];
+	for i in 0..files {
+		let path = addon_dir.join(format!("lua/bench/{}/{i}.lua", i % 64));
+		std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+		std::fs::write(path, &contents).unwrap();
+	}
+
+	addon_dir
+}
+
+fn bench_create(dir: &Path, addon_dir: &Path, max_io_threads: usize, to_file: bool) -> Duration {
+	let config = CreateGmaConfig {
+		folder: addon_dir.to_owned(),
+		max_io_threads: NonZeroUsize::new(max_io_threads).unwrap(),
+		..Default::default()
+	};
+
+	let mut total = Duration::ZERO;
+	for i in 0..ITERATIONS {
+		let gma_path = dir.join(format!("out-{max_io_threads}-{to_file}-{i}.gma"));
+
+		let start = Instant::now();
+		let mut w = BufWriter::new(File::create(&gma_path).unwrap());
+		if to_file {
+			fastgmad::create::create_gma_to_file(&config, &mut w).unwrap();
+		} else {
+			fastgmad::create::seekable_create_gma(&config, &mut w).unwrap();
+		}
+		drop(w);
+		total += start.elapsed();
+
+		std::fs::remove_file(&gma_path).unwrap();
+	}
+	total / ITERATIONS
+}
+
+fn main() {
+	let dir = std::env::temp_dir().join(format!("fastgmad-bench-{}", uuid::Uuid::new_v4()));
+	std::fs::create_dir_all(&dir).unwrap();
+
+	let threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1).max(2);
+
+	for (files, file_size) in [(10_000, 256), (40_000, 64), (2_000, 16 * 1024), (20, 32 * 1024 * 1024)] {
+		let addon_dir = create_addon(&dir, files, file_size);
+
+		for max_io_threads in [1, threads] {
+			let userspace = bench_create(&dir, &addon_dir, max_io_threads, false);
+			let kernel = bench_create(&dir, &addon_dir, max_io_threads, true);
+			println!("{files} files x {file_size} bytes, {max_io_threads} threads: userspace {userspace:?}, to file {kernel:?}");
+		}
+
+		std::fs::remove_dir_all(&addon_dir).unwrap();
+	}
+
+	std::fs::remove_dir_all(&dir).ok();
+}
@@ -0,0 +1,73 @@
+//! Extraction benchmarks for GMAs with lots of small files
+//!
+//! cargo bench --package fastgmad --bench extract
+
+use fastgmad::{create::CreateGmaConfig, extract::ExtractGmaConfig};
+use std::{
+	fs::File,
+	io::{BufReader, BufWriter},
+	num::NonZeroUsize,
+	path::{Path, PathBuf},
+	time::{Duration, Instant},
+};
+
+const ITERATIONS: u32 = 5;
+
+fn create_gma(dir: &Path, files: usize, file_size: usize) -> PathBuf {
+	let addon_dir = dir.join(format!("addon-{files}x{file_size}"));
+	std::fs::create_dir_all(&addon_dir).unwrap();
+	std::fs::write(addon_dir.join("addon.json"), br#"{"title": "Benchmark"}"#).unwrap();
+
+	let contents = vec![b'-'; file_size];
+	for i in 0..files {
+		let path = addon_dir.join(format!("lua/bench/{}/{i}.lua", i % 64));
+		std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+		std::fs::write(path, &contents).unwrap();
+	}
+
+	let config = CreateGmaConfig {
+		folder: addon_dir.clone(),
+		max_io_threads: NonZeroUsize::new(1).unwrap(),
+		..Default::default()
+	};
+
+	let gma_path = addon_dir.with_extension("gma");
+	fastgmad::create::create_gma(&config, &mut BufWriter::new(File::create(&gma_path).unwrap())).unwrap();
+	std::fs::remove_dir_all(&addon_dir).unwrap();
+	gma_path
+}
+
+fn bench_extract(dir: &Path, gma_path: &Path, max_io_threads: usize) -> Duration {
+	let mut total = Duration::ZERO;
+	for i in 0..ITERATIONS {
+		let config = ExtractGmaConfig {
+			out: dir.join(format!("out-{max_io_threads}-{i}")),
+			max_io_threads: NonZeroUsize::new(max_io_threads).unwrap(),
+			..Default::default()
+		};
+
+		let start = Instant::now();
+		fastgmad::extract::extract_gma(&config, &mut BufReader::new(File::open(gma_path).unwrap())).unwrap();
+		total += start.elapsed();
+
+		std::fs::remove_dir_all(&config.out).unwrap();
+	}
+	total / ITERATIONS
+}
+
+fn main() {
+	let dir = std::env::temp_dir().join(format!("fastgmad-bench-{}", uuid::Uuid::new_v4()));
+	std::fs::create_dir_all(&dir).unwrap();
+
+	let threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1).max(2);
+
+	for (files, file_size) in [(10_000, 256), (40_000, 64), (2_000, 16 * 1024)] {
+		let gma_path = create_gma(&dir, files, file_size);
+
+		let standard = bench_extract(&dir, &gma_path, 1);
+		let parallel = bench_extract(&dir, &gma_path, threads);
+		println!("{files} files x {file_size} bytes: standard {standard:?}, parallel ({threads} threads) {parallel:?}");
+	}
+
+	std::fs::remove_dir_all(&dir).ok();
+}
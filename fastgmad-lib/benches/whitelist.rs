@@ -0,0 +1,69 @@
+//! Whitelist and ignore list matching benchmarks for addons with lots of files
+//!
+//! cargo bench --package fastgmad --bench whitelist
+
+use fastgmad::{
+	ignore::{IgnoreList, IgnoreSyntax},
+	whitelist::Whitelist,
+};
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 5;
+const FILES: usize = 300_000;
+
+fn paths() -> Vec<String> {
+	const TEMPLATES: &[&str] = &[
+		"lua/autorun/bench/{i}.lua",
+		"lua/entities/bench_{i}/shared.lua",
+		"materials/bench/{i}/texture.vtf",
+		"materials/bench/{i}/texture.vmt",
+		"models/bench/{i}.mdl",
+		"models/bench/{i}.dx90.vtx",
+		"sound/bench/{i}.ogg",
+		"gamemodes/bench/content/materials/{i}.png",
+		"gamemodes/bench/entities/weapons/{i}.lua",
+		"data_static/bench/{i}.json",
+		"src/bench/{i}.psd",
+		"lua/bench/{i}.luac",
+	];
+	(0..FILES)
+		.map(|i| TEMPLATES[i % TEMPLATES.len()].replace("{i}", &i.to_string()))
+		.collect()
+}
+
+fn bench(name: &str, paths: &[String], f: impl Fn(&str) -> bool) {
+	let mut total = Duration::ZERO;
+	let mut matched = 0;
+	for _ in 0..ITERATIONS {
+		let start = Instant::now();
+		matched = paths.iter().filter(|path| f(path)).count();
+		total += start.elapsed();
+	}
+	println!("{name:<24} {:>10.2?} ({matched} of {} paths matched)", total / ITERATIONS, paths.len());
+}
+
+fn main() {
+	let paths = paths();
+
+	let whitelist = Whitelist::builtin();
+	bench("whitelist", &paths, |path| whitelist.check(path));
+
+	let ignore = [
+		"*.psd",
+		"*.luac",
+		"src/",
+		"/build/",
+		"**/*.bak",
+		"!keep.psd",
+		"materials/**/*.tga",
+		"lua/dev/*",
+	]
+	.map(String::from)
+	.to_vec();
+
+	let gitignore = IgnoreList::new(&ignore, IgnoreSyntax::Gitignore);
+	bench("ignore (gitignore)", &paths, |path| gitignore.is_ignored(path));
+
+	let gmad = IgnoreList::new(&ignore, IgnoreSyntax::Gmad);
+	bench("ignore (gmad)", &paths, |path| gmad.is_ignored(path));
+}
@@ -0,0 +1,494 @@
+use crate::error::{fastgmad_error, fastgmad_io_error, FastGmadError};
+use serde_json::Value;
+use std::{collections::HashMap, path::Path};
+
+/// The addon types Garry's Mod accepts in addon.json's `type`
+pub const ADDON_TYPES: &[&str] = &[
+	"gamemode",
+	"map",
+	"weapon",
+	"vehicle",
+	"npc",
+	"entity",
+	"tool",
+	"effects",
+	"model",
+	"servercontent",
+];
+
+/// The tags Garry's Mod accepts in addon.json's `tags`, of which an addon can have at most [`MAX_ADDON_TAGS`]
+pub const ADDON_TAGS: &[&str] = &["fun", "roleplay", "scenic", "movie", "realism", "cartoon", "water", "comic", "build"];
+
+/// The maximum number of tags an addon can have
+pub const MAX_ADDON_TAGS: usize = 2;
+
+/// The keys fastgmad understands in addon.json
+const ADDON_JSON_KEYS: &[&str] = &["title", "description", "type", "tags", "ignore", "include", "profiles"];
+
+/// The keys in addon.json that only fastgmad understands, which aren't stored in the .GMA
+const FASTGMAD_KEYS: &[&str] = &["include", "profiles"];
+
+/// The keys fastgmad understands in a profile in addon.json's `profiles`
+const PROFILE_KEYS: &[&str] = &["title", "include", "ignore", "tags"];
+
+#[derive(serde::Deserialize)]
+pub(super) struct AddonJson {
+	#[serde(skip)]
+	pub(super) json: String,
+
+	pub(super) title: String,
+
+	#[serde(rename = "type", default)]
+	pub(super) addon_type: Option<String>,
+
+	#[serde(default)]
+	pub(super) include: Vec<String>,
+
+	#[serde(default)]
+	pub(super) ignore: Vec<String>,
+
+	#[serde(default)]
+	profiles: HashMap<String, AddonJsonProfile>,
+}
+impl AddonJson {
+	/// Reads addon.json and checks it against the schema, warning rather than erroring about schema errors if `warn_invalid` is set
+	pub(super) fn read(path: &Path, profile: Option<&str>, warn_invalid: bool) -> Result<Self, FastGmadError> {
+		Self::read_inner(path, profile, Some(warn_invalid))
+	}
+
+	/// Reads addon.json without checking it against the schema, for fixing it
+	pub(super) fn read_unvalidated(path: &Path, profile: Option<&str>) -> Result<Self, FastGmadError> {
+		Self::read_inner(path, profile, None)
+	}
+
+	/// Reads addon.json, checking it against the schema unless `warn_invalid` is `None`
+	fn read_inner(path: &Path, profile: Option<&str>, warn_invalid: Option<bool>) -> Result<Self, FastGmadError> {
+		let json = std::fs::read_to_string(path).map_err(|error| fastgmad_io_error!(while "reading addon.json", error: error, path: path))?;
+
+		let mut addon_json: AddonJson = serde_json::from_str(&json).map_err(|error| fastgmad_error!(while "parsing addon.json", error: error))?;
+
+		if let Some(warn_invalid) = warn_invalid {
+			let (errors, warnings) = validate(&json);
+			for warning in warnings {
+				log::warn!("addon.json {warning}");
+			}
+			if warn_invalid {
+				for error in errors {
+					log::warn!("addon.json {error}");
+				}
+			} else if !errors.is_empty() {
+				return Err(fastgmad_error!(error: InvalidAddonJson(errors)));
+			}
+		}
+
+		let mut stored = serde_json::from_str::<serde_json::Map<String, Value>>(&json)
+			.map_err(|error| fastgmad_error!(while "parsing addon.json", error: error))?;
+
+		if let Some(profile) = profile {
+			addon_json.apply_profile(&mut stored, profile)?;
+		}
+
+		// Keep the addon.json as it was written unless there's something to change
+		addon_json.json = if profile.is_none() && !FASTGMAD_KEYS.iter().any(|key| stored.contains_key(*key)) {
+			json
+		} else {
+			for key in FASTGMAD_KEYS {
+				stored.remove(*key);
+			}
+			serde_json::to_string_pretty(&stored).map_err(|error| fastgmad_error!(while "writing addon.json", error: error))?
+		};
+
+		Ok(addon_json)
+	}
+
+	/// Overrides fields with the ones from a profile, both in `self` and in the addon.json to store in the .GMA
+	fn apply_profile(&mut self, json: &mut serde_json::Map<String, Value>, profile: &str) -> Result<(), FastGmadError> {
+		let profile = self
+			.profiles
+			.remove(profile)
+			.ok_or_else(|| fastgmad_error!(error: UnknownProfile(profile.to_owned())))?;
+
+		if let Some(title) = profile.title {
+			self.title = title.clone();
+			json.insert("title".to_owned(), title.into());
+		}
+		if let Some(include) = profile.include {
+			self.include = include;
+		}
+		if let Some(ignore) = profile.ignore {
+			self.ignore = ignore.clone();
+			json.insert("ignore".to_owned(), ignore.into());
+		}
+		if let Some(tags) = profile.tags {
+			json.insert("tags".to_owned(), tags.into());
+		}
+
+		Ok(())
+	}
+}
+
+/// A named set of overrides in addon.json's `profiles`, selected with [`super::CreateGmaConfig::profile`]
+#[derive(serde::Deserialize)]
+struct AddonJsonProfile {
+	title: Option<String>,
+	include: Option<Vec<String>>,
+	ignore: Option<Vec<String>>,
+	tags: Option<Vec<String>>,
+}
+
+/// A problem with addon.json, and where it is in the file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddonJsonIssue {
+	/// What's wrong
+	pub message: String,
+
+	/// The line the problem is on, starting at 1
+	pub line: usize,
+
+	/// The column the problem is at, starting at 1
+	pub column: usize,
+}
+impl std::fmt::Display for AddonJsonIssue {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "line {} column {}: {}", self.line, self.column, self.message)
+	}
+}
+
+/// A step in the path to a value in a JSON document
+#[derive(Clone, Copy)]
+enum JsonPath<'a> {
+	Key(&'a str),
+	Index(usize),
+}
+
+/// Checks addon.json against the schema Garry's Mod expects, returning errors and warnings
+///
+/// `json` must already have been parsed successfully.
+fn validate(json: &str) -> (Vec<AddonJsonIssue>, Vec<AddonJsonIssue>) {
+	let mut errors = Vec::new();
+	let mut warnings = Vec::new();
+
+	let issue = |path: &[JsonPath], at_key: bool, message: String| {
+		let (key, value) = locate(json, path).unwrap_or((0, 0..0));
+		let offset = if at_key { key } else { value.start };
+
+		let before = &json[..offset];
+		let line = before.matches('\n').count() + 1;
+		let column = before[before.rfind('\n').map(|newline| newline + 1).unwrap_or(0)..].chars().count() + 1;
+
+		AddonJsonIssue { message, line, column }
+	};
+
+	let Ok(Value::Object(root)) = serde_json::from_str::<Value>(json) else {
+		return (errors, warnings);
+	};
+
+	for key in root.keys().filter(|key| !ADDON_JSON_KEYS.contains(&key.as_str())) {
+		warnings.push(issue(&[JsonPath::Key(key)], true, format!("unknown key \"{key}\"")));
+	}
+
+	match root.get("type") {
+		None => warnings.push(issue(
+			&[],
+			false,
+			format!("missing \"type\", which should be one of {}", ADDON_TYPES.join(", ")),
+		)),
+		Some(Value::String(addon_type)) if ADDON_TYPES.iter().any(|allowed| allowed.eq_ignore_ascii_case(addon_type)) => {}
+		Some(addon_type) => errors.push(issue(
+			&[JsonPath::Key("type")],
+			false,
+			format!("\"type\" is {addon_type}, but must be one of {}", ADDON_TYPES.join(", ")),
+		)),
+	}
+
+	if let Some(description) = root.get("description").filter(|description| !description.is_string()) {
+		errors.push(issue(
+			&[JsonPath::Key("description")],
+			false,
+			format!("\"description\" is {description}, but must be a string"),
+		));
+	}
+
+	if let Some(tags) = root.get("tags") {
+		validate_tags(tags, &[JsonPath::Key("tags")], &issue, &mut errors);
+	}
+
+	if let Some(Value::Object(profiles)) = root.get("profiles") {
+		for (name, profile) in profiles {
+			let Value::Object(profile) = profile else { continue };
+
+			for key in profile.keys().filter(|key| !PROFILE_KEYS.contains(&key.as_str())) {
+				warnings.push(issue(
+					&[JsonPath::Key("profiles"), JsonPath::Key(name), JsonPath::Key(key)],
+					true,
+					format!("unknown key \"{key}\" in profile \"{name}\""),
+				));
+			}
+
+			if let Some(tags) = profile.get("tags") {
+				validate_tags(
+					tags,
+					&[JsonPath::Key("profiles"), JsonPath::Key(name), JsonPath::Key("tags")],
+					&issue,
+					&mut errors,
+				);
+			}
+		}
+	}
+
+	(errors, warnings)
+}
+
+fn validate_tags(tags: &Value, path: &[JsonPath], issue: &impl Fn(&[JsonPath], bool, String) -> AddonJsonIssue, errors: &mut Vec<AddonJsonIssue>) {
+	let Value::Array(tags) = tags else {
+		errors.push(issue(path, false, format!("\"tags\" is {tags}, but must be an array")));
+		return;
+	};
+
+	if tags.len() > MAX_ADDON_TAGS {
+		errors.push(issue(
+			path,
+			false,
+			format!("\"tags\" has {} tags, but an addon can have at most {MAX_ADDON_TAGS}", tags.len()),
+		));
+	}
+
+	for (i, tag) in tags.iter().enumerate() {
+		let valid = match tag {
+			Value::String(tag) => ADDON_TAGS.iter().any(|allowed| allowed.eq_ignore_ascii_case(tag)),
+			_ => false,
+		};
+		if !valid {
+			let path = path.iter().copied().chain([JsonPath::Index(i)]).collect::<Vec<_>>();
+			errors.push(issue(&path, false, format!("tag {tag} must be one of {}", ADDON_TAGS.join(", "))));
+		}
+	}
+}
+
+/// Sets `type` and `tags` in addon.json, leaving the rest of the file as it is
+///
+/// `tags` is left alone if there are none to set.
+pub(super) fn set_type_and_tags(json: &str, addon_type: &str, tags: &[&str]) -> Option<String> {
+	let mut json = json.to_owned();
+
+	let values = [
+		("type", serde_json::to_string(addon_type).ok()?),
+		("tags", serde_json::to_string(tags).ok()?),
+	];
+	for (key, value) in values.into_iter().filter(|(key, _)| *key != "tags" || !tags.is_empty()) {
+		if let Some((_, range)) = locate(&json, &[JsonPath::Key(key)]) {
+			json.replace_range(range, &value);
+			continue;
+		}
+
+		// Add the key to the end of the object, indented like the first key
+		let (_, root) = locate(&json, &[])?;
+		let body = json.get(root.start + 1..root.end - 1)?;
+		let indent = match body.find(|c: char| !c.is_whitespace()) {
+			Some(first) => &body[body[..first].rfind('\n').map(|newline| newline + 1).unwrap_or(first)..first],
+			None => "\t",
+		};
+		let entry = format!("\"{key}\": {value}");
+		let body_end = root.start + 1 + body.trim_end().len();
+		if body_end == root.start + 1 {
+			json.replace_range(root.start + 1..root.end - 1, &format!("\n{indent}{entry}\n"));
+		} else if body.contains('\n') {
+			json.insert_str(body_end, &format!(",\n{indent}{entry}"));
+		} else {
+			json.insert_str(body_end, &format!(", {entry}"));
+		}
+	}
+
+	Some(json)
+}
+
+/// Finds the byte offset of the key and the byte range of the value at `path` in a JSON document
+///
+/// For array elements, and the document itself, the key offset is where the value starts.
+fn locate(json: &str, path: &[JsonPath]) -> Option<(usize, std::ops::Range<usize>)> {
+	let bytes = json.as_bytes();
+	let mut pos = 0;
+
+	let skip_whitespace = |pos: &mut usize| {
+		while bytes.get(*pos).is_some_and(u8::is_ascii_whitespace) {
+			*pos += 1;
+		}
+	};
+
+	// Skips a string starting at `pos`, returning it unescaped
+	let skip_string = |pos: &mut usize| -> Option<String> {
+		let start = *pos;
+		*pos += 1;
+		loop {
+			match bytes.get(*pos)? {
+				b'\\' => *pos += 2,
+				b'"' => break,
+				_ => *pos += 1,
+			}
+		}
+		*pos += 1;
+		serde_json::from_str(&json[start..*pos]).ok()
+	};
+
+	let skip_value = |pos: &mut usize| -> Option<()> {
+		let mut depth = 0usize;
+		loop {
+			match bytes.get(*pos)? {
+				b'"' => {
+					skip_string(pos)?;
+				}
+				b'{' | b'[' => {
+					depth += 1;
+					*pos += 1;
+				}
+				b'}' | b']' if depth > 0 => {
+					depth -= 1;
+					*pos += 1;
+				}
+				b',' | b'}' | b']' if depth == 0 => return Some(()),
+				_ => *pos += 1,
+			}
+			if depth == 0 && bytes.get(*pos).is_none_or(|b| matches!(b, b',' | b'}' | b']') || b.is_ascii_whitespace()) {
+				return Some(());
+			}
+		}
+	};
+
+	skip_whitespace(&mut pos);
+	let mut key_pos = pos;
+
+	for step in path {
+		match *step {
+			JsonPath::Key(wanted) => {
+				if bytes.get(pos) != Some(&b'{') {
+					return None;
+				}
+				pos += 1;
+				loop {
+					skip_whitespace(&mut pos);
+					if bytes.get(pos) != Some(&b'"') {
+						return None;
+					}
+					let key_start = pos;
+					let key = skip_string(&mut pos)?;
+					skip_whitespace(&mut pos);
+					if bytes.get(pos) != Some(&b':') {
+						return None;
+					}
+					pos += 1;
+					skip_whitespace(&mut pos);
+
+					if key == wanted {
+						key_pos = key_start;
+						break;
+					}
+
+					skip_value(&mut pos)?;
+					skip_whitespace(&mut pos);
+					if bytes.get(pos) != Some(&b',') {
+						return None;
+					}
+					pos += 1;
+				}
+			}
+
+			JsonPath::Index(wanted) => {
+				if bytes.get(pos) != Some(&b'[') {
+					return None;
+				}
+				pos += 1;
+				for _ in 0..wanted {
+					skip_whitespace(&mut pos);
+					skip_value(&mut pos)?;
+					skip_whitespace(&mut pos);
+					if bytes.get(pos) != Some(&b',') {
+						return None;
+					}
+					pos += 1;
+				}
+				skip_whitespace(&mut pos);
+				key_pos = pos;
+			}
+		}
+	}
+
+	let start = pos;
+	skip_value(&mut pos)?;
+
+	Some((key_pos, start..pos))
+}
+
+#[test]
+fn test_validate() {
+	let json = r#"{
+	"title": "Test Addon",
+	"type": "Weapon",
+	"tags": ["fun", "build"],
+	"ignore": ["*.psd"],
+	"description": "A test addon"
+}"#;
+	assert_eq!(validate(json), (vec![], vec![]));
+
+	let json = r#"{
+	"title": "Test Addon",
+	"type": "weapons",
+	"tags": ["fun", "roleplay", "bad"],
+	"author": "me",
+	"profiles": {
+		"lite": { "tags": [1], "titel": "Lite" }
+	}
+}"#;
+	let (errors, warnings) = validate(json);
+	assert_eq!(
+		errors.iter().map(ToString::to_string).collect::<Vec<_>>(),
+		[
+			"line 3 column 10: \"type\" is \"weapons\", but must be one of gamemode, map, weapon, vehicle, npc, entity, tool, effects, model, servercontent",
+			"line 4 column 10: \"tags\" has 3 tags, but an addon can have at most 2",
+			"line 4 column 30: tag \"bad\" must be one of fun, roleplay, scenic, movie, realism, cartoon, water, comic, build",
+			"line 7 column 22: tag 1 must be one of fun, roleplay, scenic, movie, realism, cartoon, water, comic, build",
+		]
+	);
+	assert_eq!(
+		warnings.iter().map(ToString::to_string).collect::<Vec<_>>(),
+		[
+			"line 5 column 2: unknown key \"author\"",
+			"line 7 column 26: unknown key \"titel\" in profile \"lite\"",
+		]
+	);
+
+	let (errors, warnings) = validate(r#"{"title": "Test Addon"}"#);
+	assert!(errors.is_empty());
+	assert_eq!(warnings.len(), 1);
+	assert_eq!((warnings[0].line, warnings[0].column), (1, 1));
+}
+
+#[test]
+fn test_locate() {
+	let json = r#" { "a": [1, {"b": "x,]}"}, "\"c"], "c\"": { "d" : null }, "e": true }"#;
+	let at = |path: &[JsonPath]| locate(json, path).map(|(key, value)| (&json[key..key + 3], &json[value]));
+	assert_eq!(at(&[]), Some((r#"{ ""#, &json[1..])));
+	assert_eq!(at(&[JsonPath::Key("a")]), Some((r#""a""#, r#"[1, {"b": "x,]}"}, "\"c"]"#)));
+	assert_eq!(at(&[JsonPath::Key("a"), JsonPath::Index(2)]), Some((r#""\""#, r#""\"c""#)));
+	assert_eq!(
+		at(&[JsonPath::Key("a"), JsonPath::Index(1), JsonPath::Key("b")]),
+		Some((r#""b""#, r#""x,]}""#))
+	);
+	assert_eq!(at(&[JsonPath::Key("c\""), JsonPath::Key("d")]), Some((r#""d""#, "null")));
+	assert_eq!(at(&[JsonPath::Key("e")]), Some((r#""e""#, "true")));
+	assert_eq!(at(&[JsonPath::Key("f")]), None);
+}
+
+#[test]
+fn test_set_type_and_tags() {
+	let json = "{\n    \"title\": \"Test Addon\",\n    \"type\": \"tool\",\n    \"ignore\": [\"*.psd\"]\n}\n";
+	assert_eq!(
+		set_type_and_tags(json, "weapon", &["fun"]).unwrap(),
+		"{\n    \"title\": \"Test Addon\",\n    \"type\": \"weapon\",\n    \"ignore\": [\"*.psd\"],\n    \"tags\": [\"fun\"]\n}\n"
+	);
+	assert_eq!(set_type_and_tags(json, "map", &[]).unwrap(), json.replace("tool", "map"));
+	assert_eq!(
+		set_type_and_tags(r#"{"tags": ["build"], "title": "x"}"#, "map", &["scenic"]).unwrap(),
+		r#"{"tags": ["scenic"], "title": "x", "type": "map"}"#
+	);
+	assert_eq!(set_type_and_tags("{ }", "map", &[]).unwrap(), "{\n\t\"type\": \"map\"\n}");
+}
@@ -1,3 +1,5 @@
+use super::{HiddenFiles, NonUtf8Names, Symlinks};
+use crate::ignore::IgnoreSyntax;
 use std::{num::NonZeroUsize, path::PathBuf};
 
 macro_rules! nonzero {
@@ -10,12 +12,14 @@ macro_rules! nonzero {
 }
 
 /// Options for .GMA creation
-#[derive(Debug)]
+///
+/// Creation can only be cancelled (with Ctrl-C) when the `binary` feature is enabled.
+#[derive(Debug, Clone)]
 pub struct CreateGmaConfig {
 	/// The folder to create a .GMA from
 	pub folder: PathBuf,
 
-	/// Whether to warn about invalid files or to throw an error
+	/// Whether to warn about invalid files, and addon.json schema errors, or to throw an error
 	pub warn_invalid: bool,
 
 	/// The maximum number of threads to use for I/O
@@ -24,15 +28,52 @@ pub struct CreateGmaConfig {
 	/// The maximum amount of memory to use for I/O
 	pub max_io_memory_usage: NonZeroUsize,
 
+	/// Convert all file paths in the .GMA to lower case
+	pub lowercase: bool,
+
+	/// Use the modification time of the newest source file as the .GMA timestamp instead of the current time
+	pub timestamp_from_files: bool,
+
+	/// How to match the globs in addon.json's `ignore` list
+	pub ignore_syntax: IgnoreSyntax,
+
+	/// Also read `.gitignore` files, as well as `.gmadignore` files, when looking for ignore rules
+	pub respect_gitignore: bool,
+
+	/// The profile in addon.json's `profiles` to create the .GMA from, which can override the addon's title, tags, `include` and `ignore` lists
+	pub profile: Option<String>,
+
+	/// What to do with hidden files
+	pub hidden_files: HiddenFiles,
+
+	/// What to do with symbolic links
+	pub symlinks: Symlinks,
+
+	/// What to do with files whose names aren't valid UTF-8
+	///
+	/// Every other file name is normalized to Unicode NFC, with empty and `.` segments removed. Files whose names contain control
+	/// characters or `..` segments are rejected.
+	pub non_utf8_names: NonUtf8Names,
+
+	/// Whitelist files whose rules are added to the built-in whitelist, see [`crate::whitelist::Whitelist::from_json`]
+	pub whitelist_files: Vec<PathBuf>,
+
+	/// Files that will never be included in the .GMA
+	///
+	/// Use this for the output file (and any temporary files) if they could be inside `folder`.
+	pub exclude: Vec<PathBuf>,
+
 	#[cfg(feature = "binary")]
 	pub noprogress: bool,
 }
 impl CreateGmaConfig {
+	/// Also returns every `-folder` that was given; `folder` is set to the first one
 	#[cfg(feature = "binary")]
-	pub fn from_args(mut args: impl Iterator<Item = std::ffi::OsString>) -> Result<(Self, CreateGmadOut), crate::util::PrintHelp> {
+	pub fn from_args(mut args: impl Iterator<Item = std::ffi::OsString>) -> Result<(Self, Vec<PathBuf>, CreateGmadOut), crate::util::PrintHelp> {
 		use crate::util::PrintHelp;
 
 		let mut config = Self::default();
+		let mut folders = Vec::new();
 		let mut out = None;
 
 		while let Some(arg) = args.next() {
@@ -67,11 +108,69 @@ impl CreateGmaConfig {
 					out = Some(CreateGmadOut::Stdout);
 				}
 				"-folder" => {
-					config.folder = args
+					folders.push(
+						args.next()
+							.filter(|folder| !folder.is_empty())
+							.map(PathBuf::from)
+							.ok_or(PrintHelp(Some("Expected a value after -folder")))?,
+					);
+				}
+				"-lowercase" => {
+					config.lowercase = true;
+				}
+				"-timestamp-from-files" => {
+					config.timestamp_from_files = true;
+				}
+				"-ignore-syntax" => {
+					config.ignore_syntax = args
+						.next()
+						.ok_or(PrintHelp(Some("Expected value for -ignore-syntax")))?
+						.to_str()
+						.and_then(|v| v.parse().ok())
+						.ok_or(PrintHelp(Some("Expected one of gitignore or gmad for -ignore-syntax")))?;
+				}
+				"-respect-gitignore" => {
+					config.respect_gitignore = true;
+				}
+				"-profile" => {
+					config.profile = Some(
+						args.next()
+							.filter(|profile| !profile.is_empty())
+							.and_then(|profile| profile.into_string().ok())
+							.ok_or(PrintHelp(Some("Expected a value after -profile")))?,
+					);
+				}
+				"-hidden-files" => {
+					config.hidden_files = args
 						.next()
-						.filter(|folder| !folder.is_empty())
-						.map(PathBuf::from)
-						.ok_or(PrintHelp(Some("Expected a value after -folder")))?;
+						.ok_or(PrintHelp(Some("Expected value for -hidden-files")))?
+						.to_str()
+						.and_then(|v| v.parse().ok())
+						.ok_or(PrintHelp(Some("Expected one of skip, include or error for -hidden-files")))?;
+				}
+				"-symlinks" => {
+					config.symlinks = args
+						.next()
+						.ok_or(PrintHelp(Some("Expected value for -symlinks")))?
+						.to_str()
+						.and_then(|v| v.parse().ok())
+						.ok_or(PrintHelp(Some("Expected one of follow, follow-within-root, skip or error for -symlinks")))?;
+				}
+				"-non-utf8-names" => {
+					config.non_utf8_names = args
+						.next()
+						.ok_or(PrintHelp(Some("Expected value for -non-utf8-names")))?
+						.to_str()
+						.and_then(|v| v.parse().ok())
+						.ok_or(PrintHelp(Some("Expected one of error, lossy or escape for -non-utf8-names")))?;
+				}
+				"-whitelist" => {
+					config.whitelist_files.push(
+						args.next()
+							.filter(|whitelist| !whitelist.is_empty())
+							.map(PathBuf::from)
+							.ok_or(PrintHelp(Some("Expected a value after -whitelist")))?,
+					);
 				}
 				"-noprogress" => {
 					config.noprogress = true;
@@ -80,11 +179,17 @@ impl CreateGmaConfig {
 			}
 		}
 
-		if config.folder.as_os_str().is_empty() {
-			return Err(PrintHelp(Some("Please provide a folder to create a GMA from")));
+		config.folder = folders
+			.first()
+			.cloned()
+			.ok_or(PrintHelp(Some("Please provide a folder to create a GMA from")))?;
+
+		let out = out.ok_or(PrintHelp(Some("Please provide an output path for GMAD creation")))?;
+		if folders.len() > 1 && !matches!(out, CreateGmadOut::Stdout) {
+			return Err(PrintHelp(Some("Multiple -folder arguments can only be used with -stdout")));
 		}
 
-		Ok((config, out.ok_or(PrintHelp(Some("Please provide an output path for GMAD creation")))?))
+		Ok((config, folders, out))
 	}
 }
 impl Default for CreateGmaConfig {
@@ -94,6 +199,16 @@ impl Default for CreateGmaConfig {
 			warn_invalid: false,
 			max_io_threads: std::thread::available_parallelism().unwrap_or_else(|_| nonzero!(NonZeroUsize::new(1))),
 			max_io_memory_usage: nonzero!(NonZeroUsize::new(2147483648)), // 2 GiB
+			lowercase: false,
+			timestamp_from_files: false,
+			ignore_syntax: IgnoreSyntax::default(),
+			respect_gitignore: false,
+			profile: None,
+			hidden_files: HiddenFiles::default(),
+			symlinks: Symlinks::default(),
+			non_utf8_names: NonUtf8Names::default(),
+			whitelist_files: Vec::new(),
+			exclude: Vec::new(),
 
 			#[cfg(feature = "binary")]
 			noprogress: false,
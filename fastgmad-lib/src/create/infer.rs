@@ -0,0 +1,274 @@
+use super::{addon_json, discover_entries, AddonJson, CreateGmaConfig, MAX_ADDON_TAGS};
+use crate::{
+	error::{fastgmad_error, fastgmad_io_error, FastGmadError},
+	ignore::IgnoreList,
+};
+
+/// An addon type or tag that fits an addon's contents, and the files that suggest it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inference {
+	/// The type or tag
+	pub value: &'static str,
+
+	/// How many files suggest it
+	pub files: usize,
+
+	/// One of the files that suggest it
+	pub example: String,
+}
+impl std::fmt::Display for Inference {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self.files {
+			1 => write!(f, "{} ({})", self.value, self.example),
+			files => write!(f, "{} ({} and {} more)", self.value, self.example, files - 1),
+		}
+	}
+}
+
+/// The addon types and tags that fit an addon's contents
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AddonSuggestion {
+	/// Every type that fits the addon, most likely first
+	pub types: Vec<Inference>,
+
+	/// The tags that likely fit the addon, at most [`MAX_ADDON_TAGS`]
+	pub tags: Vec<Inference>,
+}
+impl AddonSuggestion {
+	/// The most likely type of the addon, if its contents suggest one
+	pub fn addon_type(&self) -> Option<&Inference> {
+		self.types.first()
+	}
+
+	/// Whether `addon_type` fits the addon's contents
+	///
+	/// Any type fits an addon whose contents don't suggest one.
+	pub fn fits(&self, addon_type: &str) -> bool {
+		self.types.is_empty() || self.types.iter().any(|inference| inference.value.eq_ignore_ascii_case(addon_type))
+	}
+}
+
+/// Addon types, from the most specific to the least
+///
+/// A tool addon usually has entities too, so the more specific type is the likelier one.
+const TYPES_BY_SPECIFICITY: &[&str] = &["gamemode", "map", "tool", "vehicle", "npc", "weapon", "entity", "effects", "model"];
+
+/// The type of an addon that only has content, such as materials and sounds
+///
+/// Weapon and map addons usually have content too, so this is only suggested when no other type is.
+const CONTENT_TYPE: &str = "servercontent";
+
+/// Tags, most likely first, and the addon types or paths that suggest them
+const TAG_HINTS: &[(&str, TagHint)] = &[
+	("build", TagHint::Type("tool")),
+	("roleplay", TagHint::PathContains("darkrp")),
+	("scenic", TagHint::Type("map")),
+	("fun", TagHint::Type("weapon")),
+	("fun", TagHint::Type("vehicle")),
+	("water", TagHint::PathContains("water")),
+];
+
+enum TagHint {
+	Type(&'static str),
+	PathContains(&'static str),
+}
+
+/// The addon type a file suggests, if any
+fn type_of(path: &str) -> Option<&'static str> {
+	let path = path.to_ascii_lowercase();
+	let addon_type = if path.strip_prefix("gamemodes/").is_some_and(|path| path.contains('/')) {
+		"gamemode"
+	} else if path.starts_with("maps/") && path.ends_with(".bsp") {
+		"map"
+	} else if path.starts_with("lua/weapons/gmod_tool/stools/") {
+		"tool"
+	} else if path.starts_with("scripts/vehicles/") {
+		"vehicle"
+	} else if path.starts_with("lua/entities/npc_") {
+		"npc"
+	} else if path.starts_with("lua/weapons/") {
+		"weapon"
+	} else if path.starts_with("lua/entities/") {
+		"entity"
+	} else if path.starts_with("lua/effects/") || path.starts_with("particles/") {
+		"effects"
+	} else if path.starts_with("models/") && path.ends_with(".mdl") {
+		"model"
+	} else {
+		return None;
+	};
+	Some(addon_type)
+}
+
+/// Infers the type and tags of an addon from the paths of the files in it
+pub fn infer_addon<'a>(paths: impl IntoIterator<Item = &'a str>) -> AddonSuggestion {
+	let mut types: Vec<Inference> = Vec::new();
+	let mut content: Vec<Inference> = Vec::new();
+	let mut tags: Vec<Inference> = Vec::new();
+
+	fn add(inferences: &mut Vec<Inference>, value: &'static str, path: &str) {
+		match inferences.iter_mut().find(|inference| inference.value == value) {
+			Some(inference) => inference.files += 1,
+			None => inferences.push(Inference {
+				value,
+				files: 1,
+				example: path.to_owned(),
+			}),
+		}
+	}
+
+	for path in paths {
+		let addon_type = type_of(path);
+		if let Some(addon_type) = addon_type {
+			add(&mut types, addon_type, path);
+		} else if !path.to_ascii_lowercase().starts_with("lua/") {
+			add(&mut content, CONTENT_TYPE, path);
+		}
+
+		for (tag, hint) in TAG_HINTS {
+			let hinted = match hint {
+				TagHint::Type(hinted_type) => addon_type == Some(*hinted_type),
+				TagHint::PathContains(needle) => path.to_ascii_lowercase().contains(needle),
+			};
+			if hinted {
+				add(&mut tags, tag, path);
+				break;
+			}
+		}
+	}
+
+	types.sort_by_key(|inference| TYPES_BY_SPECIFICITY.iter().position(|addon_type| *addon_type == inference.value));
+	if types.is_empty() {
+		types = content;
+	}
+	tags.sort_by_key(|inference| TAG_HINTS.iter().position(|(tag, _)| *tag == inference.value));
+	tags.truncate(MAX_ADDON_TAGS);
+
+	AddonSuggestion { types, tags }
+}
+
+/// Infers the type and tags of the addon in [`CreateGmaConfig::folder`] from the files that would be packed into its .GMA
+///
+/// Unlike creation, this doesn't need the addon to have a valid addon.json, or one at all.
+pub fn suggest_addon(conf: &CreateGmaConfig) -> Result<AddonSuggestion, FastGmadError> {
+	let addon_json_path = conf.folder.join("addon.json");
+	let (include, ignore) = if addon_json_path.is_file() {
+		let addon_json = AddonJson::read_unvalidated(&addon_json_path, conf.profile.as_deref())?;
+		(addon_json.include, addon_json.ignore)
+	} else {
+		Default::default()
+	};
+
+	let entries = discover_entries(
+		conf,
+		IgnoreList::new(&include, conf.ignore_syntax),
+		IgnoreList::new(&ignore, conf.ignore_syntax),
+	)?;
+
+	Ok(infer_addon(entries.iter().map(|entry| entry.relative_path.as_str())))
+}
+
+/// Writes the suggested type and tags into the addon's addon.json, creating it if it doesn't exist
+///
+/// Tags already in addon.json are kept, and suggested tags are only added while there are fewer than [`MAX_ADDON_TAGS`].
+pub fn write_suggestion(conf: &CreateGmaConfig, suggestion: &AddonSuggestion) -> Result<(), FastGmadError> {
+	let addon_type = suggestion
+		.addon_type()
+		.ok_or_else(|| fastgmad_error!(while "writing addon.json", error: NoAddonTypeInferred))?;
+
+	let addon_json_path = conf.folder.join("addon.json");
+	let json = if addon_json_path.is_file() {
+		std::fs::read_to_string(&addon_json_path)
+			.map_err(|error| fastgmad_io_error!(while "reading addon.json", error: error, path: addon_json_path))?
+	} else {
+		let title = conf
+			.folder
+			.canonicalize()
+			.ok()
+			.and_then(|folder| Some(folder.file_name()?.to_string_lossy().into_owned()))
+			.unwrap_or_default();
+		format!("{{\n\t\"title\": {}\n}}\n", serde_json::Value::from(title))
+	};
+
+	let existing_tags = serde_json::from_str::<serde_json::Value>(&json)
+		.ok()
+		.and_then(|json| {
+			Some(
+				json.get("tags")?
+					.as_array()?
+					.iter()
+					.filter_map(|tag| tag.as_str().map(str::to_owned))
+					.collect::<Vec<String>>(),
+			)
+		})
+		.unwrap_or_default();
+
+	let mut tags = existing_tags.iter().map(String::as_str).collect::<Vec<_>>();
+	for tag in &suggestion.tags {
+		if tags.len() >= MAX_ADDON_TAGS {
+			break;
+		}
+		if !tags.iter().any(|existing| existing.eq_ignore_ascii_case(tag.value)) {
+			tags.push(tag.value);
+		}
+	}
+
+	// Leave the tags alone if there's nothing to add
+	if tags.len() == existing_tags.len() {
+		tags.clear();
+	}
+
+	let json = addon_json::set_type_and_tags(&json, addon_type.value, &tags).ok_or_else(|| {
+		fastgmad_io_error!(while "writing addon.json", error: std::io::Error::new(std::io::ErrorKind::InvalidData, "addon.json is not a JSON object"), path: addon_json_path)
+	})?;
+
+	std::fs::write(&addon_json_path, json).map_err(|error| fastgmad_io_error!(while "writing addon.json", error: error, path: addon_json_path))
+}
+
+#[test]
+fn test_infer_addon() {
+	let suggestion = infer_addon([
+		"lua/weapons/weapon_a.lua",
+		"lua/weapons/weapon_b/shared.lua",
+		"lua/entities/ammo_a.lua",
+		"models/weapons/w_a.mdl",
+		"materials/models/weapons/a.vmt",
+	]);
+	assert_eq!(
+		suggestion.types.iter().map(|inference| inference.value).collect::<Vec<_>>(),
+		["weapon", "entity", "model"]
+	);
+	assert_eq!(
+		suggestion.addon_type().unwrap().to_string(),
+		"weapon (lua/weapons/weapon_a.lua and 1 more)"
+	);
+	assert_eq!(suggestion.tags.iter().map(|inference| inference.value).collect::<Vec<_>>(), ["fun"]);
+	assert!(suggestion.fits("Entity"));
+	assert!(!suggestion.fits("map"));
+
+	let suggestion = infer_addon(["maps/rp_waterfront.bsp", "maps/rp_waterfront.nav", "materials/a.vtf"]);
+	assert_eq!(suggestion.addon_type().unwrap().value, "map");
+	assert_eq!(
+		suggestion.tags.iter().map(|inference| inference.value).collect::<Vec<_>>(),
+		["scenic", "water"]
+	);
+
+	let suggestion = infer_addon(["gamemodes/darkrp/gamemode/init.lua", "lua/weapons/gmod_tool/stools/a.lua"]);
+	assert_eq!(
+		suggestion.types.iter().map(|inference| inference.value).collect::<Vec<_>>(),
+		["gamemode", "tool"]
+	);
+	assert_eq!(
+		suggestion.tags.iter().map(|inference| inference.value).collect::<Vec<_>>(),
+		["build", "roleplay"]
+	);
+
+	let suggestion = infer_addon(["materials/a.vmt", "sound/a.wav", "lua/autorun/a.lua"]);
+	assert_eq!(suggestion.addon_type().unwrap().to_string(), "servercontent (materials/a.vmt and 1 more)");
+	assert_eq!(suggestion.types.len(), 1);
+	assert!(!suggestion.fits("weapon"));
+
+	let suggestion = infer_addon(["lua/autorun/a.lua"]);
+	assert_eq!(suggestion, AddonSuggestion::default());
+	assert!(suggestion.fits("weapon"));
+}
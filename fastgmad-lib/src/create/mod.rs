@@ -1,35 +1,73 @@
 use crate::{
 	error::{fastgmad_error, fastgmad_io_error, FastGmadError},
+	ignore::{IgnoreFiles, IgnoreList},
 	util::{self, WriteEx},
-	whitelist,
+	whitelist::{Whitelist, WhitelistViolation},
 };
 use std::{
+	borrow::Cow,
+	collections::{hash_map, HashMap, HashSet},
 	fs::File,
-	io::{Read, SeekFrom},
+	io::{BufWriter, Read, SeekFrom},
 	io::{Seek, Write},
 	path::{Path, PathBuf},
 	sync::Arc,
-	sync::{atomic::AtomicUsize, Condvar, Mutex},
+	sync::{
+		atomic::{AtomicBool, AtomicUsize},
+		mpsc::Receiver,
+		Condvar, Mutex,
+	},
 	time::SystemTime,
 };
 
 mod conf;
 pub use conf::CreateGmaConfig;
 
+mod addon_json;
+use addon_json::AddonJson;
+pub use addon_json::{AddonJsonIssue, ADDON_TAGS, ADDON_TYPES, MAX_ADDON_TAGS};
+
+mod infer;
+pub use infer::{infer_addon, suggest_addon, write_suggestion, AddonSuggestion, Inference};
+
+mod normalize;
+pub use normalize::{PathCollision, PathProblem, RenameReason, UnrepresentablePath};
+
+mod policy;
+pub use policy::{HiddenFiles, NonUtf8Names, Symlinks};
+
+mod walk;
+
 #[cfg(feature = "binary")]
 pub use conf::CreateGmadOut;
 
 /// Creates a GMA file from a directory.
 ///
-/// Prefer [`seekable_create_gma`] if your writer type implements [`std::io::Seek`], as it supports parallel I/O.
+/// Prefer [`seekable_create_gma`] if your writer type implements [`std::io::Seek`], as entries can then be written out of order.
 pub fn create_gma(conf: &CreateGmaConfig, w: &mut impl Write) -> Result<(), FastGmadError> {
-	StandardCreateGma::create_gma_with_done_callback(conf, w, &mut || ())
+	if conf.max_io_threads.get() == 1 {
+		StandardCreateGma::create_gma_with_done_callback(conf, &mut UserspaceWriter(w), &mut || ())
+	} else {
+		PipelinedCreateGma::create_gma_with_done_callback(conf, w, &mut || ())
+	}
 }
 
 /// Creates a GMA file from a directory.
 ///
-/// Prefer this function over [`create_gma`] if your writer type implements [`std::io::Seek`], as this function supports parallel I/O.
+/// Prefer this function over [`create_gma`] if your writer type implements [`std::io::Seek`], as entries can then be written out of order.
 pub fn seekable_create_gma(conf: &CreateGmaConfig, w: &mut (impl Write + Seek)) -> Result<(), FastGmadError> {
+	let w = &mut UserspaceWriter(w);
+	if conf.max_io_threads.get() == 1 {
+		StandardCreateGma::create_gma_with_done_callback(conf, w, &mut || ())
+	} else {
+		ParallelCreateGma::create_gma_with_done_callback(conf, w, &mut || ())
+	}
+}
+
+/// Creates a GMA file from a directory.
+///
+/// Prefer this function over [`seekable_create_gma`] if you're writing to a file, as entries are then copied into it inside the kernel where possible.
+pub fn create_gma_to_file(conf: &CreateGmaConfig, w: &mut BufWriter<File>) -> Result<(), FastGmadError> {
 	if conf.max_io_threads.get() == 1 {
 		StandardCreateGma::create_gma_with_done_callback(conf, w, &mut || ())
 	} else {
@@ -39,7 +77,12 @@ pub fn seekable_create_gma(conf: &CreateGmaConfig, w: &mut (impl Write + Seek))
 
 #[cfg(feature = "binary")]
 pub fn create_gma_with_done_callback(conf: &CreateGmaConfig, w: &mut impl Write, done_callback: &mut dyn FnMut()) -> Result<(), FastGmadError> {
-	StandardCreateGma::create_gma_with_done_callback(conf, w, done_callback)
+	let _ctrlc_handle = crate::ctrlc_handling::CtrlCHandle::get();
+	if conf.max_io_threads.get() == 1 {
+		StandardCreateGma::create_gma_with_done_callback(conf, &mut UserspaceWriter(w), done_callback)
+	} else {
+		PipelinedCreateGma::create_gma_with_done_callback(conf, w, done_callback)
+	}
 }
 
 #[cfg(feature = "binary")]
@@ -48,6 +91,22 @@ pub fn seekable_create_gma_with_done_callback(
 	w: &mut (impl Write + Seek),
 	done_callback: &mut dyn FnMut(),
 ) -> Result<(), FastGmadError> {
+	let _ctrlc_handle = crate::ctrlc_handling::CtrlCHandle::get();
+	let w = &mut UserspaceWriter(w);
+	if conf.max_io_threads.get() == 1 {
+		StandardCreateGma::create_gma_with_done_callback(conf, w, done_callback)
+	} else {
+		ParallelCreateGma::create_gma_with_done_callback(conf, w, done_callback)
+	}
+}
+
+#[cfg(feature = "binary")]
+pub fn create_gma_to_file_with_done_callback(
+	conf: &CreateGmaConfig,
+	w: &mut BufWriter<File>,
+	done_callback: &mut dyn FnMut(),
+) -> Result<(), FastGmadError> {
+	let _ctrlc_handle = crate::ctrlc_handling::CtrlCHandle::get();
 	if conf.max_io_threads.get() == 1 {
 		StandardCreateGma::create_gma_with_done_callback(conf, w, done_callback)
 	} else {
@@ -55,13 +114,82 @@ pub fn seekable_create_gma_with_done_callback(
 	}
 }
 
+/// A GMA being written, which entry files are copied into
+trait EntryWriter: Write {
+	/// Copies up to `size` bytes of an entry's file to the writer's cursor, `size` being the size it had when it was discovered
+	fn copy_entry(&mut self, f: &mut File, size: u64) -> Result<u64, std::io::Error>;
+}
+impl EntryWriter for BufWriter<File> {
+	fn copy_entry(&mut self, f: &mut File, size: u64) -> Result<u64, std::io::Error> {
+		#[cfg(target_os = "linux")]
+		let copied = if size >= util::KERNEL_COPY_MIN_LEN {
+			// The kernel writes at the file's cursor, so anything buffered has to go first
+			self.flush()?;
+			let copied = util::kernel_copy(f, 0, self.get_ref(), size)?;
+			f.seek(SeekFrom::Start(copied))?;
+			copied
+		} else {
+			0
+		};
+		#[cfg(not(target_os = "linux"))]
+		let copied = 0;
+
+		// Copy whatever the kernel couldn't through userspace
+		Ok(copied + std::io::copy(&mut f.take(size - copied), self)?)
+	}
+}
+
+/// Copies entries through userspace, for writers that aren't files
+struct UserspaceWriter<'a, W>(&'a mut W);
+impl<W: Write> Write for UserspaceWriter<'_, W> {
+	fn write(&mut self, buf: &[u8]) -> Result<usize, std::io::Error> {
+		self.0.write(buf)
+	}
+
+	fn write_all(&mut self, buf: &[u8]) -> Result<(), std::io::Error> {
+		self.0.write_all(buf)
+	}
+
+	fn flush(&mut self) -> Result<(), std::io::Error> {
+		self.0.flush()
+	}
+}
+impl<W: Seek> Seek for UserspaceWriter<'_, W> {
+	fn seek(&mut self, pos: SeekFrom) -> Result<u64, std::io::Error> {
+		self.0.seek(pos)
+	}
+
+	fn stream_position(&mut self) -> Result<u64, std::io::Error> {
+		self.0.stream_position()
+	}
+}
+impl<W: Write> EntryWriter for UserspaceWriter<'_, W> {
+	fn copy_entry(&mut self, f: &mut File, size: u64) -> Result<u64, std::io::Error> {
+		std::io::copy(&mut f.take(size), self.0)
+	}
+}
+
 trait CreateGma<W: Write> {
 	fn create_gma_with_done_callback(conf: &CreateGmaConfig, w: &mut W, done_callback: &mut dyn FnMut()) -> Result<(), FastGmadError> {
 		log::info!("Reading addon.json...");
-		let addon_json = AddonJson::read(&conf.folder.join("addon.json"))?;
+		let addon_json = AddonJson::read(&conf.folder.join("addon.json"), conf.profile.as_deref(), conf.warn_invalid)?;
 
 		log::info!("Discovering entries...");
-		let entries = discover_entries(&conf.folder, &addon_json.ignore, conf.warn_invalid)?;
+		let entries = discover_entries(
+			conf,
+			IgnoreList::new(&addon_json.include, conf.ignore_syntax),
+			IgnoreList::new(&addon_json.ignore, conf.ignore_syntax),
+		)?;
+
+		if let Some(declared) = &addon_json.addon_type {
+			let suggestion = infer_addon(entries.iter().map(|entry| entry.relative_path.as_str()));
+			if let Some(inferred) = suggestion.addon_type().filter(|_| !suggestion.fits(declared)) {
+				log::warn!(
+					"addon.json says the addon is a {declared}, but its contents look like a {inferred} - run fastgmad suggest -folder {} to check",
+					conf.folder.display()
+				);
+			}
+		}
 
 		log::info!("Writing GMA metadata...");
 
@@ -78,11 +206,22 @@ trait CreateGma<W: Write> {
 			.map_err(|error| fastgmad_io_error!(while "writing SteamID", error: error))?;
 
 		// Timestamp
-		w.write_all(&u64::to_le_bytes(
+		let timestamp = if conf.timestamp_from_files {
+			// Use the newest source file, so that rebuilding unchanged sources gives the same timestamp
+			let addon_json_modified = std::fs::metadata(conf.folder.join("addon.json"))
+				.and_then(|metadata| metadata.modified())
+				.ok();
+			entries
+				.iter()
+				.filter_map(|entry| entry.modified)
+				.chain(addon_json_modified)
+				.max()
+				.unwrap_or_else(SystemTime::now)
+		} else {
 			SystemTime::now()
-				.duration_since(SystemTime::UNIX_EPOCH)
-				.map(|dur| dur.as_secs())
-				.unwrap_or(0),
+		};
+		w.write_all(&u64::to_le_bytes(
+			timestamp.duration_since(SystemTime::UNIX_EPOCH).map(|dur| dur.as_secs()).unwrap_or(0),
 		))
 		.map_err(|err| fastgmad_io_error!(while "writing timestamp", error: err))?;
 
@@ -170,73 +309,263 @@ trait CreateGma<W: Write> {
 	) -> Result<(), FastGmadError>;
 }
 
-fn discover_entries(folder: &Path, ignore: &[String], warn_invalid: bool) -> Result<Vec<GmaFileEntry>, FastGmadError> {
-	let mut entries = Vec::new();
-	let mut prev_offset = 0;
-	for entry in walkdir::WalkDir::new(folder).follow_links(true).sort_by_file_name() {
-		let entry = entry.map_err(|error| {
-			let path = error.path().unwrap_or(folder).to_owned();
-			if let Some(io_error) = error.into_io_error() {
-				fastgmad_io_error!(while "walking directory", error: io_error, path: path)
-			} else {
-				fastgmad_io_error!(while "walking directory", error: std::io::Error::new(std::io::ErrorKind::Other, "unknown"), path: path)
+fn discover_entries(conf: &CreateGmaConfig, include: IgnoreList, ignore: IgnoreList) -> Result<Vec<GmaFileEntry>, FastGmadError> {
+	let folder = conf.folder.as_path();
+
+	// Paths that must never end up in the GMA, relative to the addon folder
+	let exclude = if conf.exclude.is_empty() {
+		HashSet::new()
+	} else {
+		let canonical_folder = folder
+			.canonicalize()
+			.map_err(|error| fastgmad_io_error!(while "resolving addon directory", error: error, path: folder))?;
+
+		conf.exclude
+			.iter()
+			.filter_map(|path| {
+				// The file might not exist yet, so resolve its parent instead
+				let canonical_path = path
+					.parent()
+					.filter(|parent| !parent.as_os_str().is_empty())
+					.unwrap_or(Path::new("."))
+					.canonicalize()
+					.ok()?
+					.join(path.file_name()?);
+				Some(canonical_path.strip_prefix(&canonical_folder).ok()?.to_str()?.replace('\\', "/"))
+			})
+			.collect::<HashSet<_>>()
+	};
+
+	// Restore the original names of files that were escaped during extraction
+	let escaped_names_path = folder.join(crate::ESCAPED_NAMES_FILE);
+	let escaped_names = if escaped_names_path.is_file() {
+		let json = std::fs::read(&escaped_names_path)
+			.map_err(|error| fastgmad_io_error!(while "reading escaped file names", error: error, path: escaped_names_path))?;
+		serde_json::from_slice::<HashMap<String, String>>(&json).map_err(|error| fastgmad_error!(while "parsing escaped file names", error: error))?
+	} else {
+		HashMap::new()
+	};
+
+	let ignore = IgnoreFiles::new(folder, ignore, conf.respect_gitignore);
+
+	let whitelist = Whitelist::with_files(&conf.whitelist_files)?;
+
+	enum Discovered {
+		Entry(GmaFileEntry, String, Option<RenameReason>),
+		NotWhitelisted(String),
+		Hidden(String),
+		Unrepresentable(UnrepresentablePath),
+	}
+
+	// Filter the files as we find them on the walker's threads, but report them in order afterwards
+	let discovered = walk::walk_files(folder, conf.max_io_threads.get(), conf.symlinks, |path, metadata| {
+		let relative = path.strip_prefix(folder).map_err(
+			|_| fastgmad_io_error!(error: std::io::Error::new(std::io::ErrorKind::InvalidData, "File not in addon directory"), path: path),
+		)?;
+		let (disk_path, valid_utf8) = normalize::decode_path(relative.as_os_str(), conf.non_utf8_names);
+		let disk_path = disk_path.replace('\\', "/");
+
+		if disk_path == "addon.json"
+			|| disk_path == crate::ESCAPED_NAMES_FILE
+			|| IgnoreFiles::is_ignore_file(&disk_path)
+			|| exclude.contains(&disk_path)
+		{
+			return Ok(None);
+		}
+
+		// How the path in the GMA came to differ from the path on disk, in case it collides with another file's
+		let mut renamed = (!valid_utf8).then_some(RenameReason::NotUtf8(conf.non_utf8_names));
+
+		let mut relative_path = match escaped_names.get(&disk_path) {
+			Some(original) => {
+				renamed = renamed.or(Some(RenameReason::EscapedName));
+				original.clone()
 			}
-		})?;
+			None => disk_path.clone(),
+		};
 
-		if !entry.file_type().is_file() {
-			continue;
+		// Reported once we know the file isn't ignored
+		let mut problem = (!valid_utf8 && conf.non_utf8_names == NonUtf8Names::Error).then_some(PathProblem::NotUtf8);
+		match normalize::normalize_path(&relative_path) {
+			Ok(Cow::Borrowed(_)) => {}
+			Ok(Cow::Owned(normalized)) => {
+				renamed = renamed.or(Some(RenameReason::Normalized));
+				relative_path = normalized;
+			}
+			Err(normalize_problem) => problem = problem.or(Some(normalize_problem)),
 		}
 
-		let path = entry.path();
-		if matches!(util::is_hidden_file(path), Ok(true) | Err(_)) {
-			continue;
+		if conf.lowercase {
+			let lowercase_path = relative_path.to_lowercase();
+			if lowercase_path != relative_path {
+				renamed = renamed.or(Some(RenameReason::Lowercase));
+				relative_path = lowercase_path;
+			}
 		}
 
-		let relative_path = path
-			.strip_prefix(folder)
-			.map_err(|_| fastgmad_io_error!(error: std::io::Error::new(std::io::ErrorKind::InvalidData, "File not in addon directory"), path: path))?
-			.to_str()
-			.ok_or_else(|| fastgmad_io_error!(error: std::io::Error::new(std::io::ErrorKind::InvalidData, "File path not valid UTF-8"), path: path))?
-			.replace('\\', "/");
+		// If there's an include list, only files it matches are packed
+		if !include.is_empty() && !include.is_ignored(&relative_path) {
+			return Ok(None);
+		}
 
-		if relative_path == "addon.json" {
-			continue;
+		if ignore.is_ignored(&disk_path, &relative_path)? {
+			return Ok(None);
 		}
 
-		if whitelist::is_ignored(&relative_path, ignore) {
-			continue;
+		if conf.hidden_files != HiddenFiles::Include && util::is_hidden_file(path, metadata) {
+			return Ok(Some(Discovered::Hidden(disk_path)));
 		}
 
-		if !whitelist::check(&relative_path) {
-			if warn_invalid {
-				log::warn!(
-					"File {} not in GMA whitelist - see https://wiki.facepunch.com/gmod/Workshop_Addon_Creation",
-					relative_path
-				);
-				continue;
+		if let Some(problem) = problem {
+			return Ok(Some(Discovered::Unrepresentable(UnrepresentablePath { path: disk_path, problem })));
+		}
+
+		if !whitelist.check(&relative_path) {
+			return Ok(Some(Discovered::NotWhitelisted(relative_path)));
+		}
+
+		Ok(Some(Discovered::Entry(
+			GmaFileEntry {
+				path: path.to_owned(),
+				relative_path,
+				size: metadata.len(),
+				offset: 0,
+				modified: metadata.modified().ok(),
+			},
+			// Escaped names would look the same as the names they collide with
+			if valid_utf8 {
+				disk_path
 			} else {
-				return Err(fastgmad_error!(error: EntryNotWhitelisted(relative_path)));
+				relative.to_string_lossy().replace('\\', "/")
+			},
+			renamed,
+		)))
+	})?;
+
+	const MAX_HIDDEN_WARNINGS: usize = 10;
+
+	let mut entries = Vec::with_capacity(discovered.len());
+	let mut prev_offset = 0;
+	let mut hidden = 0;
+	let mut not_whitelisted = Vec::new();
+	let mut unrepresentable = Vec::new();
+	let mut packed_paths: HashMap<String, (String, Option<RenameReason>)> = HashMap::with_capacity(discovered.len());
+	for discovered in discovered {
+		let mut entry = match discovered {
+			Discovered::Entry(entry, disk_path, renamed) => {
+				match packed_paths.entry(entry.relative_path.clone()) {
+					hash_map::Entry::Occupied(other) => {
+						let (other_disk_path, other_renamed) = other.get();
+						if let Some(reason) = renamed.or(*other_renamed) {
+							return Err(fastgmad_error!(error: PathCollision(normalize::PathCollision {
+								paths: [other_disk_path.clone(), disk_path],
+								path: entry.relative_path,
+								reason,
+							})));
+						}
+					}
+					hash_map::Entry::Vacant(vacant) => {
+						vacant.insert((disk_path, renamed));
+					}
+				}
+				entry
 			}
-		}
+			Discovered::NotWhitelisted(relative_path) => {
+				let violation = WhitelistViolation::new(relative_path, &whitelist);
+				if conf.warn_invalid {
+					log::warn!(
+						"File {} not in GMA whitelist - see https://wiki.facepunch.com/gmod/Workshop_Addon_Creation",
+						violation
+					);
+				} else {
+					not_whitelisted.push(violation);
+				}
+				continue;
+			}
+			Discovered::Unrepresentable(path) => {
+				if conf.warn_invalid {
+					log::warn!("Skipping file {path}");
+				} else {
+					unrepresentable.push(path);
+				}
+				continue;
+			}
+			Discovered::Hidden(relative_path) => {
+				if conf.hidden_files == HiddenFiles::Error {
+					return Err(fastgmad_error!(error: HiddenFile(relative_path)));
+				}
+				if hidden < MAX_HIDDEN_WARNINGS {
+					log::warn!("Skipping hidden file {}", relative_path);
+				}
+				hidden += 1;
+				continue;
+			}
+		};
 
-		let size = std::fs::metadata(path)
-			.map_err(|error| fastgmad_io_error!(while "reading entry metadata", error: error, path: path))?
-			.len();
+		let new_offset = prev_offset + entry.size;
+		entry.offset = core::mem::replace(&mut prev_offset, new_offset);
+		entries.push(entry);
+	}
 
-		let new_offset = prev_offset + size;
+	if !unrepresentable.is_empty() {
+		return Err(fastgmad_error!(error: UnrepresentablePaths(unrepresentable)));
+	}
 
-		entries.push(GmaFileEntry {
-			path: path.to_owned(),
-			relative_path,
-			size,
-			offset: core::mem::replace(&mut prev_offset, new_offset),
-		});
+	if !not_whitelisted.is_empty() {
+		return Err(fastgmad_error!(error: EntriesNotWhitelisted(not_whitelisted)));
+	}
+
+	if hidden > MAX_HIDDEN_WARNINGS {
+		log::warn!("...and {} more hidden files", hidden - MAX_HIDDEN_WARNINGS);
+	}
+	if hidden > 0 {
+		log::warn!("Use -hidden-files include to pack hidden files, or ignore them in addon.json or a .gmadignore to silence this warning");
 	}
+
+	check_case(&entries)?;
+
 	Ok(entries)
 }
 
+/// Garry's Mod's filesystem is case-insensitive on Windows but case-sensitive on Linux servers
+fn check_case(entries: &[GmaFileEntry]) -> Result<(), FastGmadError> {
+	const MAX_MIXED_CASE_WARNINGS: usize = 10;
+
+	let mut lowercase_paths = HashMap::with_capacity(entries.len());
+	let mut mixed_case = 0;
+	for entry in entries {
+		let lowercase_path = entry.relative_path.to_lowercase();
+
+		if lowercase_path != entry.relative_path {
+			if mixed_case < MAX_MIXED_CASE_WARNINGS {
+				log::warn!(
+					"File {} has upper case characters in its path, which can break on case-sensitive file systems (Linux servers)",
+					entry.relative_path
+				);
+			}
+			mixed_case += 1;
+		}
+
+		if let Some(other) = lowercase_paths.insert(lowercase_path, entry.relative_path.as_str()) {
+			return Err(fastgmad_error!(error: CaseInsensitiveCollision(other.to_string(), entry.relative_path.clone())));
+		}
+	}
+
+	if mixed_case > MAX_MIXED_CASE_WARNINGS {
+		log::warn!(
+			"...and {} more files with upper case characters in their paths",
+			mixed_case - MAX_MIXED_CASE_WARNINGS
+		);
+	}
+	if mixed_case > 0 {
+		log::warn!("Use -lowercase to convert all paths in the GMA to lower case");
+	}
+
+	Ok(())
+}
+
 struct StandardCreateGma;
-impl<W: Write> CreateGma<W> for StandardCreateGma {
+impl<W: EntryWriter> CreateGma<W> for StandardCreateGma {
 	fn write_entries(
 		_conf: &CreateGmaConfig,
 		w: &mut W,
@@ -251,11 +580,20 @@ impl<W: Write> CreateGma<W> for StandardCreateGma {
 		};
 
 		for entry in entries.iter() {
-			std::io::copy(
-				&mut File::open(&entry.path).map_err(|error| fastgmad_io_error!(while "opening GMA entry file", error: error, path: entry.path))?,
-				w,
-			)
-			.map_err(|error| fastgmad_io_error!(while "copying GMA entry data", error: error, path: entry.path))?;
+			#[cfg(feature = "binary")]
+			if crate::ctrlc_handling::cancelled() {
+				return Err(fastgmad_error!(error: Aborted));
+			}
+
+			// Copied inside the kernel if `w` is a file
+			let copied = w
+				.copy_entry(
+					&mut File::open(&entry.path)
+						.map_err(|error| fastgmad_io_error!(while "opening GMA entry file", error: error, path: entry.path))?,
+					entry.size,
+				)
+				.map_err(|error| fastgmad_io_error!(while "copying GMA entry data", error: error, path: entry.path))?;
+			check_entry_size(entry, copied)?;
 
 			#[cfg(feature = "binary")]
 			if let Some(progress) = &mut progress {
@@ -268,7 +606,7 @@ impl<W: Write> CreateGma<W> for StandardCreateGma {
 }
 
 struct ParallelCreateGma;
-impl<W: Write + Seek> CreateGma<W> for ParallelCreateGma {
+impl<W: EntryWriter + Seek> CreateGma<W> for ParallelCreateGma {
 	fn write_entries(
 		conf: &CreateGmaConfig,
 		w: &mut W,
@@ -300,13 +638,32 @@ impl<W: Write + Seek> CreateGma<W> for ParallelCreateGma {
 			entries: Vec<&'a GmaFileEntry>,
 			memory_usage: Mutex<usize>,
 			memory_usage_cvar: Condvar,
+			stopped: AtomicBool,
 		}
 		impl EntriesQueue<'_> {
 			pub fn next(&self) -> Option<&GmaFileEntry> {
+				if self.stopped() {
+					return None;
+				}
+
 				// NOTE: technically this can wrap around on overflow, but it won't happen because
 				// we only spawn a maximum of MAX_IO_THREADS.
 				self.entries.get(self.head.fetch_add(1, std::sync::atomic::Ordering::SeqCst)).copied()
 			}
+
+			pub fn stopped(&self) -> bool {
+				#[cfg(feature = "binary")]
+				if crate::ctrlc_handling::cancelled() {
+					return true;
+				}
+
+				self.stopped.load(std::sync::atomic::Ordering::SeqCst)
+			}
+
+			pub fn stop(&self) {
+				self.stopped.store(true, std::sync::atomic::Ordering::SeqCst);
+				self.memory_usage_cvar.notify_all();
+			}
 		}
 
 		let queue = Arc::new(EntriesQueue {
@@ -314,6 +671,7 @@ impl<W: Write + Seek> CreateGma<W> for ParallelCreateGma {
 			head: AtomicUsize::new(0),
 			memory_usage: Mutex::new(0),
 			memory_usage_cvar: Condvar::new(),
+			stopped: AtomicBool::new(false),
 		});
 		std::thread::scope(|scope| {
 			const IO_THREAD_STACK_SIZE: usize = 2048;
@@ -341,10 +699,15 @@ impl<W: Write + Seek> CreateGma<W> for ParallelCreateGma {
 								let mut memory_usage = queue
 									.memory_usage_cvar
 									.wait_while(queue.memory_usage.lock().unwrap(), |memory_usage| {
-										*memory_usage > 0 && *memory_usage + *size as usize >= conf.max_io_memory_usage.get()
+										!queue.stopped.load(std::sync::atomic::Ordering::SeqCst)
+											&& *memory_usage > 0 && *memory_usage + *size as usize >= conf.max_io_memory_usage.get()
 									})
 									.unwrap();
 
+								if queue.stopped() {
+									return;
+								}
+
 								let bytes_left = max_offset - cur_offset;
 								let offset = cur_offset;
 
@@ -378,7 +741,21 @@ impl<W: Write + Seek> CreateGma<W> for ParallelCreateGma {
 			}
 			drop(tx);
 
-			while let Ok(res) = rx.recv() {
+			// Stop the I/O threads if we bail out early, otherwise they'd be stuck waiting on us forever
+			struct StopOnDrop<'a, 'b>(&'a EntriesQueue<'b>, Receiver<Result<(u64, Vec<u8>), FastGmadError>>);
+			impl Drop for StopOnDrop<'_, '_> {
+				fn drop(&mut self) {
+					self.0.stop();
+				}
+			}
+			let rx = StopOnDrop(&queue, rx);
+
+			while let Ok(res) = rx.1.recv() {
+				#[cfg(feature = "binary")]
+				if crate::ctrlc_handling::cancelled() {
+					return Err(fastgmad_error!(error: Aborted));
+				}
+
 				let (offset, contents) = res?;
 
 				w.seek(SeekFrom::Start(contents_ptr + offset))
@@ -401,14 +778,22 @@ impl<W: Write + Seek> CreateGma<W> for ParallelCreateGma {
 		})?;
 
 		for entry in full_copy_entries.iter() {
+			#[cfg(feature = "binary")]
+			if crate::ctrlc_handling::cancelled() {
+				return Err(fastgmad_error!(error: Aborted));
+			}
+
 			w.seek(SeekFrom::Start(contents_ptr + entry.offset))
 				.map_err(|error| fastgmad_io_error!(while "seeking to GMA entry offset", error: error))?;
 
-			std::io::copy(
-				&mut File::open(&entry.path).map_err(|error| fastgmad_io_error!(while "opening GMA entry file", error: error, path: entry.path))?,
-				w,
-			)
-			.map_err(|error| fastgmad_io_error!(while "copying GMA entry data", error: error, path: entry.path))?;
+			let copied = w
+				.copy_entry(
+					&mut File::open(&entry.path)
+						.map_err(|error| fastgmad_io_error!(while "opening GMA entry file", error: error, path: entry.path))?,
+					entry.size,
+				)
+				.map_err(|error| fastgmad_io_error!(while "copying GMA entry data", error: error, path: entry.path))?;
+			check_entry_size(entry, copied)?;
 
 			#[cfg(feature = "binary")]
 			if let Some(progress) = &mut progress {
@@ -422,25 +807,239 @@ impl<W: Write + Seek> CreateGma<W> for ParallelCreateGma {
 	}
 }
 
-#[derive(serde::Deserialize)]
-struct AddonJson {
-	#[serde(skip)]
-	json: String,
+struct PipelinedCreateGma;
+impl<W: Write> CreateGma<W> for PipelinedCreateGma {
+	fn write_entries(
+		conf: &CreateGmaConfig,
+		w: &mut W,
+		#[cfg(feature = "binary")] total_size: u64,
+		entries: &[GmaFileEntry],
+	) -> Result<(), FastGmadError> {
+		#[cfg(feature = "binary")]
+		let mut progress = if !conf.noprogress {
+			Some(crate::util::ProgressPrinter::new(total_size))
+		} else {
+			None
+		};
 
-	title: String,
+		// The I/O threads read upcoming entries into memory ahead of time, and this thread writes them out in order
+		enum Prefetched {
+			Buffered(Vec<u8>),
+			TooBig,
+			Failed(FastGmadError),
+		}
 
-	#[serde(default)]
-	ignore: Vec<String>,
-}
-impl AddonJson {
-	fn read(path: &Path) -> Result<Self, FastGmadError> {
-		let json = std::fs::read_to_string(path).map_err(|error| fastgmad_io_error!(while "reading addon.json", error: error, path: path))?;
+		struct PipelineState {
+			next_write: usize,
+			memory_usage: usize,
+			prefetched: HashMap<usize, Prefetched>,
+		}
+
+		struct Pipeline {
+			head: AtomicUsize,
+			state: Mutex<PipelineState>,
+			cvar: Condvar,
+			stopped: AtomicBool,
+		}
+		impl Pipeline {
+			fn stopped(&self) -> bool {
+				#[cfg(feature = "binary")]
+				if crate::ctrlc_handling::cancelled() {
+					return true;
+				}
 
-		let mut addon_json: AddonJson = serde_json::from_str(&json).map_err(|error| fastgmad_error!(while "parsing addon.json", error: error))?;
+				self.stopped.load(std::sync::atomic::Ordering::SeqCst)
+			}
 
-		addon_json.json = json;
+			fn stop(&self) {
+				self.stopped.store(true, std::sync::atomic::Ordering::SeqCst);
+				self.cvar.notify_all();
+			}
 
-		Ok(addon_json)
+			fn prefetched(&self, i: usize, prefetched: Prefetched) {
+				self.state.lock().unwrap().prefetched.insert(i, prefetched);
+				self.cvar.notify_all();
+			}
+		}
+
+		let pipeline = Pipeline {
+			head: AtomicUsize::new(0),
+			state: Mutex::new(PipelineState {
+				next_write: 0,
+				memory_usage: 0,
+				prefetched: HashMap::new(),
+			}),
+			cvar: Condvar::new(),
+			stopped: AtomicBool::new(false),
+		};
+
+		let serial = std::thread::scope(|scope| {
+			const IO_THREAD_STACK_SIZE: usize = 2048;
+
+			// Stop the I/O threads if we bail out early, otherwise they'd be stuck waiting on us forever
+			struct StopOnDrop<'a>(&'a Pipeline);
+			impl Drop for StopOnDrop<'_> {
+				fn drop(&mut self) {
+					self.0.stop();
+				}
+			}
+			let _stop = StopOnDrop(&pipeline);
+
+			for spawned in 0..entries.len().min(conf.max_io_threads.get()) {
+				let pipeline = &pipeline;
+				let res = std::thread::Builder::new()
+					.stack_size(IO_THREAD_STACK_SIZE)
+					.spawn_scoped(scope, move || loop {
+						let i = pipeline.head.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+						let GmaFileEntry { path, size, .. } = match entries.get(i) {
+							Some(entry) => entry,
+							None => break,
+						};
+						let size = *size as usize;
+
+						if size > conf.max_io_memory_usage.get() {
+							// Too big to buffer, the writer will copy it itself
+							pipeline.prefetched(i, Prefetched::TooBig);
+							continue;
+						}
+
+						// Wait until there's enough memory available to buffer this entry,
+						// unless the writer is waiting on it, otherwise we could deadlock with the entries ahead of it
+						let mut state = pipeline
+							.cvar
+							.wait_while(pipeline.state.lock().unwrap(), |state| {
+								!pipeline.stopped()
+									&& state.next_write != i && state.memory_usage > 0
+									&& state.memory_usage + size > conf.max_io_memory_usage.get()
+							})
+							.unwrap();
+
+						if pipeline.stopped() {
+							break;
+						}
+
+						state.memory_usage += size;
+						drop(state);
+
+						let res = File::open(path)
+							.and_then(|f| {
+								let mut buf = Vec::with_capacity(size);
+								f.take(size as u64).read_to_end(&mut buf).map(|_| buf)
+							})
+							.map_err(|error| fastgmad_io_error!(while "reading GMA entry data", error: error, path: path));
+
+						pipeline.prefetched(
+							i,
+							match res {
+								Ok(buf) => Prefetched::Buffered(buf),
+								Err(err) => Prefetched::Failed(err),
+							},
+						);
+					});
+
+				match res {
+					Ok(_) => {}
+
+					// Nothing would ever prefetch the entries, so write them ourselves
+					Err(_) if spawned == 0 => return Ok(true),
+
+					Err(error) => return Err(fastgmad_io_error!(while "spawning I/O thread", error: error)),
+				}
+			}
+
+			for (i, entry) in entries.iter().enumerate() {
+				#[cfg(feature = "binary")]
+				if crate::ctrlc_handling::cancelled() {
+					return Err(fastgmad_error!(error: Aborted));
+				}
+
+				let prefetched = {
+					let mut state = pipeline
+						.cvar
+						.wait_while(pipeline.state.lock().unwrap(), |state| {
+							!pipeline.stopped() && !state.prefetched.contains_key(&i)
+						})
+						.unwrap();
+
+					match state.prefetched.remove(&i) {
+						Some(prefetched) => prefetched,
+						None => {
+							#[cfg(feature = "binary")]
+							if crate::ctrlc_handling::cancelled() {
+								return Err(fastgmad_error!(error: Aborted));
+							}
+
+							return Err(fastgmad_io_error!(
+								while "reading GMA entry data",
+								error: std::io::Error::other("the I/O threads stopped before reading it"),
+								path: entry.path
+							));
+						}
+					}
+				};
+
+				let buffered = match prefetched {
+					Prefetched::Failed(err) => return Err(err),
+
+					Prefetched::Buffered(contents) => {
+						check_entry_size(entry, contents.len() as u64)?;
+						w.write_all(&contents)
+							.map_err(|error| fastgmad_io_error!(while "writing GMA entry data", error: error))?;
+						entry.size as usize
+					}
+
+					Prefetched::TooBig => {
+						let f = File::open(&entry.path)
+							.map_err(|error| fastgmad_io_error!(while "opening GMA entry file", error: error, path: entry.path))?;
+						let copied = std::io::copy(&mut f.take(entry.size), w)
+							.map_err(|error| fastgmad_io_error!(while "copying GMA entry data", error: error, path: entry.path))?;
+						check_entry_size(entry, copied)?;
+						0
+					}
+				};
+
+				{
+					let mut state = pipeline.state.lock().unwrap();
+					state.memory_usage -= buffered;
+					state.next_write = i + 1;
+				}
+				pipeline.cvar.notify_all();
+
+				#[cfg(feature = "binary")]
+				if let Some(progress) = &mut progress {
+					progress.add_progress(entry.size);
+				}
+			}
+
+			Ok::<_, FastGmadError>(false)
+		})?;
+
+		if serial {
+			return StandardCreateGma::write_entries(
+				conf,
+				&mut UserspaceWriter(w),
+				#[cfg(feature = "binary")]
+				total_size,
+				entries,
+			);
+		}
+
+		w.flush().map_err(|error| fastgmad_io_error!(while "flushing GMA file", error: error))?;
+
+		Ok(())
+	}
+}
+
+/// The header has already promised `entry.size` bytes, so an entry file that changed size since it was discovered would corrupt the GMA
+fn check_entry_size(entry: &GmaFileEntry, copied: u64) -> Result<(), FastGmadError> {
+	if copied == entry.size {
+		Ok(())
+	} else {
+		Err(fastgmad_io_error!(
+			while "copying GMA entry data",
+			error: std::io::Error::new(std::io::ErrorKind::InvalidData, format!("the file was {} bytes when it was discovered, but {copied} bytes were read", entry.size)),
+			path: entry.path
+		))
 	}
 }
 
@@ -449,4 +1048,78 @@ struct GmaFileEntry {
 	relative_path: String,
 	size: u64,
 	offset: u64,
+	modified: Option<SystemTime>,
+}
+
+#[test]
+fn test_discover_entries() {
+	let folder = std::env::temp_dir().join(format!("fastgmad-discover-{}", uuid::Uuid::new_v4()));
+	for i in 0..50 {
+		let path = folder.join(format!("lua/autorun/{}/{i}.lua", i % 7));
+		std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+		std::fs::write(path, b"print('a')").unwrap();
+	}
+
+	let discover = |threads: usize, symlinks: Symlinks| {
+		let conf = CreateGmaConfig {
+			folder: folder.clone(),
+			max_io_threads: std::num::NonZeroUsize::new(threads).unwrap(),
+			symlinks,
+			..Default::default()
+		};
+		discover_entries(&conf, IgnoreList::new(&[], conf.ignore_syntax), IgnoreList::new(&[], conf.ignore_syntax))
+			.map(|entries| entries.into_iter().map(|entry| entry.relative_path).collect::<Vec<_>>())
+	};
+
+	let entries = discover(1, Symlinks::default()).unwrap();
+	assert_eq!(entries.len(), 50);
+	assert!(entries.windows(2).all(|pair| pair[0] < pair[1]), "{entries:?}");
+	for _ in 0..5 {
+		assert_eq!(discover(8, Symlinks::default()).unwrap(), entries);
+	}
+
+	// Sibling directories that link to each other never point at an ancestor of the link itself
+	#[cfg(unix)]
+	{
+		std::os::unix::fs::symlink(folder.join("lua/autorun/1"), folder.join("lua/autorun/0/link")).unwrap();
+		std::os::unix::fs::symlink(folder.join("lua/autorun/0"), folder.join("lua/autorun/1/link")).unwrap();
+		for threads in [1, 8] {
+			let error = discover(threads, Symlinks::Follow).unwrap_err().to_string();
+			assert!(error.contains("File system loop found"), "{error}");
+		}
+	}
+
+	std::fs::remove_dir_all(&folder).unwrap();
+}
+
+#[test]
+fn test_copy_entry() {
+	let dir = std::env::temp_dir().join(format!("fastgmad-copy-entry-{}", uuid::Uuid::new_v4()));
+	std::fs::create_dir_all(&dir).unwrap();
+
+	// The file has grown since it was discovered, so only the discovered size may be copied
+	let contents = (0..256 * 1024).map(|i| i as u8).collect::<Vec<_>>();
+	std::fs::write(dir.join("entry"), &contents).unwrap();
+
+	for size in [10, util::KERNEL_COPY_MIN_LEN + 10] {
+		let mut w = BufWriter::new(File::create(dir.join("gma")).unwrap());
+		w.write_all(b"header").unwrap();
+
+		let copied = w.copy_entry(&mut File::open(dir.join("entry")).unwrap(), size).unwrap();
+		let mut userspace = Vec::new();
+		let userspace_copied = UserspaceWriter(&mut userspace)
+			.copy_entry(&mut File::open(dir.join("entry")).unwrap(), size)
+			.unwrap();
+		w.write_all(b"footer").unwrap();
+		drop(w);
+
+		assert_eq!((copied, userspace_copied), (size, size));
+		assert_eq!(userspace, contents[..size as usize]);
+		assert_eq!(
+			std::fs::read(dir.join("gma")).unwrap(),
+			[b"header".as_slice(), &contents[..size as usize], b"footer"].concat()
+		);
+	}
+
+	std::fs::remove_dir_all(&dir).unwrap();
 }
@@ -0,0 +1,182 @@
+use super::NonUtf8Names;
+use std::{borrow::Cow, ffi::OsStr};
+use unicode_normalization::UnicodeNormalization;
+
+/// Why a file's path can't be stored in a .GMA
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathProblem {
+	/// The path isn't valid UTF-8 (or valid UTF-16 on Windows) and [`NonUtf8Names::Error`] was set
+	NotUtf8,
+
+	/// The path contains a control character
+	ControlCharacter(char),
+
+	/// The path contains a `..` segment
+	ParentSegment,
+}
+impl std::fmt::Display for PathProblem {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::NotUtf8 => f.write_str("isn't valid UTF-8. Use -non-utf8-names lossy or escape to pack it anyway"),
+			Self::ControlCharacter(c) => write!(f, "contains the control character U+{:04X}", *c as u32),
+			Self::ParentSegment => f.write_str("contains a .. segment"),
+		}
+	}
+}
+
+/// A file whose path can't be stored in a .GMA
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnrepresentablePath {
+	/// The path of the file, relative to the addon folder
+	pub path: String,
+
+	/// Why it can't be stored
+	pub problem: PathProblem,
+}
+impl std::fmt::Display for UnrepresentablePath {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{} {}", self.path.escape_debug(), self.problem)
+	}
+}
+
+/// Why two files would be packed under the same path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenameReason {
+	/// A name wasn't valid UTF-8 and was converted with the [`NonUtf8Names`] policy
+	NotUtf8(NonUtf8Names),
+
+	/// A name was restored from the escaped file names recorded during extraction
+	EscapedName,
+
+	/// A name was converted to Unicode NFC or had empty or `.` segments removed
+	Normalized,
+
+	/// [`crate::create::CreateGmaConfig::lowercase`] was set
+	Lowercase,
+}
+impl std::fmt::Display for RenameReason {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::NotUtf8(NonUtf8Names::Escape) => {
+				f.write_str("a name that isn't valid UTF-8 had its invalid bytes escaped (-non-utf8-names escape)")
+			}
+			Self::NotUtf8(_) => f.write_str("a name that isn't valid UTF-8 had its invalid bytes replaced (-non-utf8-names lossy)"),
+			Self::EscapedName => write!(f, "a name was restored from {}", crate::ESCAPED_NAMES_FILE),
+			Self::Normalized => f.write_str("their names are the same once converted to Unicode NFC and with empty and . segments removed"),
+			Self::Lowercase => f.write_str("-lowercase was set"),
+		}
+	}
+}
+
+/// Two files that would be packed under the same path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathCollision {
+	/// The paths of the files, relative to the addon folder
+	pub paths: [String; 2],
+
+	/// The path both would be stored under
+	pub path: String,
+
+	/// Why they would be stored under the same path
+	pub reason: RenameReason,
+}
+impl std::fmt::Display for PathCollision {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(
+			f,
+			"Files {} and {} would both be packed as {} because {}",
+			self.paths[0].escape_debug(),
+			self.paths[1].escape_debug(),
+			self.path.escape_debug(),
+			self.reason
+		)
+	}
+}
+
+/// Converts a path to a string, returning whether it was valid UTF-8
+pub(crate) fn decode_path(path: &OsStr, non_utf8_names: NonUtf8Names) -> (Cow<'_, str>, bool) {
+	if let Some(path) = path.to_str() {
+		return (Cow::Borrowed(path), true);
+	}
+
+	let path = match non_utf8_names {
+		NonUtf8Names::Error | NonUtf8Names::Lossy => path.to_string_lossy(),
+		NonUtf8Names::Escape => {
+			let mut escaped = String::new();
+			for chunk in path.as_encoded_bytes().utf8_chunks() {
+				// Escape % too, so that the escaped bytes can't be confused with a literal %
+				escaped.push_str(&chunk.valid().replace('%', "%25"));
+				for byte in chunk.invalid() {
+					escaped.push_str(&format!("%{byte:02X}"));
+				}
+			}
+			Cow::Owned(escaped)
+		}
+	};
+	(path, false)
+}
+
+/// Normalizes a `/` separated path for storing in a .GMA
+///
+/// The path is converted to Unicode NFC, which is what Lua source files are almost always written in, while macOS gives
+/// file names in NFD. Empty and `.` segments are removed.
+pub(crate) fn normalize_path(path: &str) -> Result<Cow<'_, str>, PathProblem> {
+	if let Some(c) = path.chars().find(|c| c.is_control()) {
+		return Err(PathProblem::ControlCharacter(c));
+	}
+
+	let segments = path.split('/').filter(|segment| !matches!(*segment, "" | "."));
+	if segments.clone().any(|segment| segment == "..") {
+		return Err(PathProblem::ParentSegment);
+	}
+
+	let is_clean = segments.clone().count() == path.split('/').count();
+	let is_nfc = unicode_normalization::is_nfc(path);
+	if is_clean && is_nfc {
+		return Ok(Cow::Borrowed(path));
+	}
+
+	let path = segments.collect::<Vec<_>>().join("/");
+	if is_nfc {
+		Ok(Cow::Owned(path))
+	} else {
+		Ok(Cow::Owned(path.nfc().collect()))
+	}
+}
+
+#[test]
+fn test_normalize_path() {
+	assert!(matches!(
+		normalize_path("lua/autorun/test.lua"),
+		Ok(Cow::Borrowed("lua/autorun/test.lua"))
+	));
+	assert_eq!(normalize_path("lua//autorun/./test.lua/").unwrap(), "lua/autorun/test.lua");
+	assert_eq!(normalize_path("./sound/caf\u{65}\u{301}.wav").unwrap(), "sound/caf\u{e9}.wav");
+	assert_eq!(normalize_path("sound/caf\u{e9}.wav").unwrap(), "sound/caf\u{e9}.wav");
+	assert_eq!(normalize_path("lua/../test.lua"), Err(PathProblem::ParentSegment));
+	assert_eq!(normalize_path("lua/te\nst.lua"), Err(PathProblem::ControlCharacter('\n')));
+	assert_eq!(normalize_path("lua/test\u{7f}.lua"), Err(PathProblem::ControlCharacter('\u{7f}')));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_decode_non_utf8() {
+	use std::os::unix::ffi::OsStrExt;
+
+	let path = OsStr::from_bytes(b"lua/caf\xe9.lua");
+	assert_eq!(decode_path(path, NonUtf8Names::Error), (Cow::Borrowed("lua/caf\u{fffd}.lua"), false));
+	assert_eq!(decode_path(path, NonUtf8Names::Lossy), (Cow::Borrowed("lua/caf\u{fffd}.lua"), false));
+	assert_eq!(decode_path(path, NonUtf8Names::Escape), (Cow::Borrowed("lua/caf%E9.lua"), false));
+	assert_eq!(
+		decode_path(OsStr::from_bytes(b"lua/100%\xe9.lua"), NonUtf8Names::Escape),
+		(Cow::Borrowed("lua/100%25%E9.lua"), false)
+	);
+	assert_eq!(
+		decode_path(OsStr::new("lua/100%.lua"), NonUtf8Names::Escape),
+		(Cow::Borrowed("lua/100%.lua"), true)
+	);
+	assert_eq!(
+		decode_path(OsStr::new("lua/café.lua"), NonUtf8Names::Escape),
+		(Cow::Borrowed("lua/café.lua"), true)
+	);
+}
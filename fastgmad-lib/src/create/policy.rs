@@ -0,0 +1,85 @@
+/// What to do with hidden files in the addon folder
+///
+/// Hidden files are files whose names start with a `.` on Unix, or that have the hidden attribute on Windows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HiddenFiles {
+	/// Leave them out of the .GMA, reporting them in the log
+	#[default]
+	Skip,
+
+	/// Treat them like any other file
+	Include,
+
+	/// Fail the creation
+	Error,
+}
+impl std::str::FromStr for HiddenFiles {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"skip" => Ok(Self::Skip),
+			"include" => Ok(Self::Include),
+			"error" => Ok(Self::Error),
+			_ => Err(()),
+		}
+	}
+}
+
+/// What to do with symbolic links in the addon folder
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Symlinks {
+	/// Follow them wherever they point, failing if they point to a folder that contains them
+	#[default]
+	Follow,
+
+	/// Follow them only if they point to somewhere inside the addon folder, and fail otherwise
+	FollowWithinRoot,
+
+	/// Leave them out of the .GMA
+	Skip,
+
+	/// Fail the creation
+	Error,
+}
+impl std::str::FromStr for Symlinks {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"follow" => Ok(Self::Follow),
+			"follow-within-root" => Ok(Self::FollowWithinRoot),
+			"skip" => Ok(Self::Skip),
+			"error" => Ok(Self::Error),
+			_ => Err(()),
+		}
+	}
+}
+
+/// What to do with files whose names aren't valid UTF-8, or valid UTF-16 on Windows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NonUtf8Names {
+	/// Fail the creation, reporting every such file
+	#[default]
+	Error,
+
+	/// Replace the invalid parts of the name with U+FFFD
+	Lossy,
+
+	/// Percent-encode the invalid bytes of the name, and any `%` in it, e.g. `caf%E9.lua`
+	///
+	/// Creation fails if an escaped name is the same as another file's name.
+	Escape,
+}
+impl std::str::FromStr for NonUtf8Names {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"error" => Ok(Self::Error),
+			"lossy" => Ok(Self::Lossy),
+			"escape" => Ok(Self::Escape),
+			_ => Err(()),
+		}
+	}
+}
@@ -0,0 +1,277 @@
+use super::Symlinks;
+use crate::error::{fastgmad_error, fastgmad_io_error, FastGmadError};
+use std::{
+	fs::Metadata,
+	path::{Path, PathBuf},
+	sync::{Arc, Condvar, Mutex},
+};
+
+/// A directory to walk, and the canonical paths of it and its ancestors if symlinks are followed
+type QueuedDir = (PathBuf, Option<Arc<CanonicalDir>>);
+
+/// The canonical path of a directory being walked, linked to its parent's, like walkdir's stack of ancestors
+struct CanonicalDir {
+	path: PathBuf,
+	parent: Option<Arc<CanonicalDir>>,
+}
+impl CanonicalDir {
+	fn child(self: &Arc<Self>, path: PathBuf) -> Arc<Self> {
+		Arc::new(Self {
+			path,
+			parent: Some(self.clone()),
+		})
+	}
+
+	/// Whether `path` is the canonical path of this directory or one of its ancestors
+	fn is_ancestor(&self, path: &Path) -> bool {
+		let mut dir = Some(self);
+		while let Some(ancestor) = dir {
+			if ancestor.path == path {
+				return true;
+			}
+			dir = ancestor.parent.as_deref();
+		}
+		false
+	}
+}
+
+struct WalkQueue {
+	state: Mutex<WalkQueueState>,
+	cvar: Condvar,
+}
+
+struct WalkQueueState {
+	dirs: Vec<QueuedDir>,
+	active: usize,
+	error: Option<FastGmadError>,
+}
+
+impl WalkQueue {
+	fn push(&self, dir: QueuedDir) {
+		self.state.lock().unwrap().dirs.push(dir);
+		self.cvar.notify_one();
+	}
+
+	/// Waits for a directory to walk, or returns `None` once every directory has been walked
+	fn pop(&self) -> Option<QueuedDir> {
+		let mut state = self
+			.cvar
+			.wait_while(self.state.lock().unwrap(), |state| {
+				state.dirs.is_empty() && state.active > 0 && state.error.is_none()
+			})
+			.unwrap();
+
+		if state.error.is_some() {
+			return None;
+		}
+
+		let dir = state.dirs.pop()?;
+		state.active += 1;
+		Some(dir)
+	}
+
+	fn done(&self, res: Result<(), FastGmadError>) {
+		let mut state = self.state.lock().unwrap();
+		state.active -= 1;
+		if let Err(err) = res {
+			state.error.get_or_insert(err);
+		}
+		drop(state);
+
+		self.cvar.notify_all();
+	}
+}
+
+/// Walks a directory tree on a pool of threads, handling symlinks according to `symlinks`
+///
+/// `visit` is called concurrently for every file with the metadata the walk already fetched for it.
+/// The results are sorted by path, which is the order `walkdir::WalkDir::sort_by_file_name` would visit the files in.
+pub(super) fn walk_files<T: Send>(
+	root: &Path,
+	threads: usize,
+	symlinks: Symlinks,
+	visit: impl Fn(&Path, &Metadata) -> Result<Option<T>, FastGmadError> + Sync,
+) -> Result<Vec<T>, FastGmadError> {
+	let canonical_root = match symlinks {
+		Symlinks::Follow | Symlinks::FollowWithinRoot => Some(
+			root.canonicalize()
+				.map_err(|error| fastgmad_io_error!(while "resolving addon directory", error: error, path: root))?,
+		),
+		Symlinks::Skip | Symlinks::Error => None,
+	};
+	let root_dir = canonical_root.clone().map(|path| Arc::new(CanonicalDir { path, parent: None }));
+	let walk = Walk {
+		symlinks,
+		canonical_root: canonical_root.filter(|_| symlinks == Symlinks::FollowWithinRoot),
+		visit,
+	};
+
+	let queue = WalkQueue {
+		state: Mutex::new(WalkQueueState {
+			dirs: vec![(root.to_path_buf(), root_dir)],
+			active: 0,
+			error: None,
+		}),
+		cvar: Condvar::new(),
+	};
+
+	let mut files = std::thread::scope(|scope| {
+		let workers = (0..threads.max(1))
+			.map(|_| {
+				scope.spawn(|| {
+					let mut files = Vec::new();
+					while let Some((dir, canonical_dir)) = queue.pop() {
+						queue.done(walk.walk_dir(&queue, &dir, canonical_dir.as_ref(), &mut files));
+					}
+					files
+				})
+			})
+			.collect::<Vec<_>>();
+
+		workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect::<Vec<_>>()
+	});
+
+	if let Some(err) = queue.state.into_inner().unwrap().error {
+		return Err(err);
+	}
+
+	files.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+
+	Ok(files.into_iter().map(|(_, file)| file).collect())
+}
+
+struct Walk<F> {
+	symlinks: Symlinks,
+
+	/// Set if symlinks must stay inside the root
+	canonical_root: Option<PathBuf>,
+
+	visit: F,
+}
+impl<F> Walk<F> {
+	fn walk_dir<T>(
+		&self,
+		queue: &WalkQueue,
+		dir: &Path,
+		canonical_dir: Option<&Arc<CanonicalDir>>,
+		files: &mut Vec<(PathBuf, T)>,
+	) -> Result<(), FastGmadError>
+	where
+		F: Fn(&Path, &Metadata) -> Result<Option<T>, FastGmadError>,
+	{
+		#[cfg(feature = "binary")]
+		if crate::ctrlc_handling::cancelled() {
+			return Err(fastgmad_error!(error: Aborted));
+		}
+
+		let read_dir = std::fs::read_dir(dir).map_err(|error| fastgmad_io_error!(while "walking directory", error: error, path: dir))?;
+		for entry in read_dir {
+			let entry = entry.map_err(|error| fastgmad_io_error!(while "walking directory", error: error, path: dir))?;
+			let path = entry.path();
+
+			// The file type usually comes for free with the directory listing, so we only stat symlinks and files
+			let file_type = entry
+				.file_type()
+				.map_err(|error| fastgmad_io_error!(while "walking directory", error: error, path: path))?;
+
+			let (metadata, canonical_child) = if file_type.is_symlink() {
+				match self.symlinks {
+					Symlinks::Follow | Symlinks::FollowWithinRoot => {}
+					Symlinks::Skip => {
+						log::info!("Skipping symbolic link {}", path.display());
+						continue;
+					}
+					Symlinks::Error => return Err(fastgmad_error!(error: SymlinkNotAllowed(path))),
+				}
+
+				let metadata = std::fs::metadata(&path).map_err(|error| fastgmad_io_error!(while "walking directory", error: error, path: path))?;
+
+				// Symlinks to files only need resolving if they have to stay inside the root
+				if self.canonical_root.is_none() && !metadata.is_dir() {
+					(metadata, None)
+				} else {
+					let target = path
+						.canonicalize()
+						.map_err(|error| fastgmad_io_error!(while "walking directory", error: error, path: path))?;
+
+					if let Some(canonical_root) = &self.canonical_root {
+						if !target.starts_with(canonical_root) {
+							return Err(fastgmad_error!(error: SymlinkOutsideAddon { link: path, target }));
+						}
+					}
+
+					// Don't follow symlinks back into a directory we're already in, including through other symlinks
+					if metadata.is_dir() && canonical_dir.is_some_and(|canonical_dir| canonical_dir.is_ancestor(&target)) {
+						return Err(fastgmad_io_error!(
+							while "walking directory",
+							error: std::io::Error::other(format!("File system loop found: {} points to an ancestor {}", path.display(), target.display())),
+							path: path
+						));
+					}
+
+					(metadata, canonical_dir.map(|canonical_dir| canonical_dir.child(target)))
+				}
+			} else if file_type.is_file() {
+				let metadata = entry
+					.metadata()
+					.map_err(|error| fastgmad_io_error!(while "reading entry metadata", error: error, path: path))?;
+				(metadata, None)
+			} else {
+				if file_type.is_dir() {
+					let canonical_child = canonical_dir.map(|canonical_dir| canonical_dir.child(canonical_dir.path.join(entry.file_name())));
+					queue.push((path, canonical_child));
+				}
+				continue;
+			};
+
+			if metadata.is_dir() {
+				queue.push((path, canonical_child));
+			} else if metadata.is_file() {
+				if let Some(file) = (self.visit)(&path, &metadata)? {
+					files.push((path, file));
+				}
+			}
+		}
+
+		Ok(())
+	}
+}
+
+#[test]
+fn test_walk_files() {
+	let root = std::env::temp_dir().join(format!("fastgmad-walk-{}", uuid::Uuid::new_v4()));
+	for path in ["a.lua", "a/b.lua", "a/c/d.lua", "a-b/e.lua", "a.b/f.lua", "B/g.lua", "b/h.lua", "z.lua"] {
+		let path = root.join(path);
+		std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+		std::fs::write(path, b"").unwrap();
+	}
+
+	let expected = walkdir::WalkDir::new(&root)
+		.sort_by_file_name()
+		.into_iter()
+		.map(|entry| entry.unwrap())
+		.filter(|entry| entry.file_type().is_file())
+		.map(|entry| entry.into_path())
+		.collect::<Vec<_>>();
+
+	for threads in [1, 4] {
+		let files = walk_files(&root, threads, Symlinks::Follow, |path, _| Ok(Some(path.to_path_buf()))).unwrap();
+		assert_eq!(files, expected);
+	}
+
+	#[cfg(unix)]
+	{
+		std::os::unix::fs::symlink(root.join("a.b"), root.join("a-b/sibling")).unwrap();
+		let files = walk_files(&root, 4, Symlinks::Follow, |path, _| Ok(Some(path.to_path_buf()))).unwrap();
+		assert!(files.contains(&root.join("a-b/sibling/f.lua")));
+
+		std::os::unix::fs::symlink(root.join("a-b"), root.join("a.b/sibling")).unwrap();
+		assert!(walk_files(&root, 4, Symlinks::Follow, |path, _| Ok(Some(path.to_path_buf()))).is_err());
+		std::fs::remove_file(root.join("a.b/sibling")).unwrap();
+
+		std::os::unix::fs::symlink(root.join("a"), root.join("a/c/loop")).unwrap();
+		assert!(walk_files(&root, 4, Symlinks::Follow, |path, _| Ok(Some(path.to_path_buf()))).is_err());
+	}
+
+	std::fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,70 @@
+// cargo build --package fastgmad-publish --features binary && cargo run --package fastgmad-bin -- publish -addon C:\Users\William\Documents\GitHub\fastgmad\fastgmad-lib\test_data\wiremod.gma
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static CTRL_C_INSTALLED: std::sync::Once = std::sync::Once::new();
+static CTRL_C_HANDLES: std::sync::Mutex<usize> = std::sync::Mutex::new(0);
+
+// Polled from hot loops, so it's kept out of the mutex
+static CTRL_C_PRESSED: AtomicBool = AtomicBool::new(false);
+
+pub fn exit_ctrlc() -> ! {
+	std::process::exit(if cfg!(windows) {
+		-1073741510
+	} else if cfg!(unix) {
+		130
+	} else {
+		1
+	});
+}
+
+/// Whether the process has been asked to stop (Ctrl-C or SIGTERM) while a [`CtrlCHandle`] was alive
+///
+/// Long running loops should poll this and bail out with [`FastGmadErrorKind::Aborted`](crate::error::FastGmadErrorKind::Aborted), cleaning up after themselves.
+pub fn cancelled() -> bool {
+	CTRL_C_PRESSED.load(Ordering::Relaxed)
+}
+
+/// While a handle is alive, Ctrl-C and SIGTERM don't exit the process immediately; instead, the holder is expected to poll [`cancelled`] and clean up
+pub struct CtrlCHandle;
+impl CtrlCHandle {
+	pub fn get() -> Self {
+		let mut handles = CTRL_C_HANDLES.lock().unwrap();
+
+		*handles += 1;
+
+		CTRL_C_INSTALLED.call_once(|| {
+			ctrlc::set_handler(|| {
+				let handles = CTRL_C_HANDLES.lock().unwrap();
+				if *handles == 0 {
+					exit_ctrlc();
+				} else {
+					if CTRL_C_PRESSED.swap(true, Ordering::Relaxed) {
+						// Already pressed, exit immediately
+						exit_ctrlc();
+					}
+					eprintln!();
+					log::warn!("Aborting, please wait...");
+				}
+			})
+			.ok();
+		});
+
+		drop(handles);
+
+		CtrlCHandle
+	}
+
+	pub fn check(&self, cleanup: impl FnOnce()) {
+		if cancelled() {
+			cleanup();
+			log::warn!("Aborted by user");
+			exit_ctrlc();
+		}
+	}
+}
+impl Drop for CtrlCHandle {
+	fn drop(&mut self) {
+		*CTRL_C_HANDLES.lock().unwrap() -= 1;
+	}
+}
@@ -26,8 +26,62 @@ impl std::error::Error for FastGmadError {}
 pub enum FastGmadErrorKind {
 	#[error("File {0} not in GMA whitelist - see https://wiki.facepunch.com/gmod/Workshop_Addon_Creation")]
 	/// GMA entry not in whitelist
+	#[deprecated = "creation reports every file that isn't whitelisted at once with EntriesNotWhitelisted"]
 	EntryNotWhitelisted(String),
 
+	#[error("{} files not in GMA whitelist - see https://wiki.facepunch.com/gmod/Workshop_Addon_Creation{}", .0.len(), .0.iter().map(|violation| format!("\n\t{violation}")).collect::<String>())]
+	/// GMA entries not in whitelist, found all at once
+	EntriesNotWhitelisted(Vec<crate::whitelist::WhitelistViolation>),
+
+	#[error("{} files can't be stored in a GMA{}", .0.len(), .0.iter().map(|path| format!("\n\t{path}")).collect::<String>())]
+	/// Files whose paths can't be stored in a GMA, found all at once
+	UnrepresentablePaths(Vec<crate::create::UnrepresentablePath>),
+
+	#[error("{0}")]
+	/// Two files would be packed under the same path once their names are converted for the GMA
+	PathCollision(crate::create::PathCollision),
+
+	#[error("Files {0} and {1} only differ by case, which will break on case-insensitive file systems (Windows)")]
+	/// Two GMA entries have the same path when compared case-insensitively
+	CaseInsensitiveCollision(String, String),
+
+	#[error("File {0} cannot be extracted on every OS (reserved name or illegal characters)")]
+	/// GMA entry file name is not portable
+	NonPortableFileName(String),
+
+	#[error("GMA entries {0} and {1} would both be extracted as {2}")]
+	/// An escaped file name is the same as another GMA entry's name, see [`crate::extract::PortableNames::Escape`]
+	EscapedNameCollision(String, String, String),
+
+	#[error("File {0} is hidden")]
+	/// A hidden file was found and [`crate::create::HiddenFiles::Error`] was set
+	HiddenFile(String),
+
+	#[error("Symbolic link {0} found")]
+	/// A symbolic link was found and [`crate::create::Symlinks::Error`] was set
+	SymlinkNotAllowed(PathBuf),
+
+	#[error("Symbolic link {link} points outside the addon folder, to {target}")]
+	/// A symbolic link points outside the addon folder and [`crate::create::Symlinks::FollowWithinRoot`] was set
+	SymlinkOutsideAddon {
+		/// The symbolic link
+		link: PathBuf,
+		/// Where it points to
+		target: PathBuf,
+	},
+
+	#[error("Invalid addon.json{}", .0.iter().map(|issue| format!("\n\t{issue}")).collect::<String>())]
+	/// addon.json doesn't match the schema Garry's Mod expects
+	InvalidAddonJson(Vec<crate::create::AddonJsonIssue>),
+
+	#[error("Profile {0} not found in addon.json")]
+	/// The requested profile isn't in addon.json's `profiles`
+	UnknownProfile(String),
+
+	#[error("Couldn't tell the addon's type from its contents")]
+	/// [`crate::create::write_suggestion`] was called for an addon whose contents don't suggest a type
+	NoAddonTypeInferred,
+
 	#[error("JSON error ({0})")]
 	/// serde_json error
 	JsonError(#[from] serde_json::Error),
@@ -56,11 +110,21 @@ pub enum FastGmadErrorKind {
 		error: std::io::Error,
 	},
 
+	#[cfg(feature = "mmap")]
+	#[error("GMA file \"{0}\" was modified while it was memory-mapped")]
+	/// A memory-mapped GMA file was modified by another process
+	MappedFileModified(PathBuf),
+
 	#[cfg(feature = "binary")]
 	#[error("Shared library error ({0})")]
 	/// Shared library error
 	Libloading(#[from] libloading::Error),
 
+	#[cfg(feature = "binary")]
+	#[error("Aborted by user")]
+	/// The operation was aborted by the user pressing Ctrl-C
+	Aborted,
+
 	#[cfg(any(feature = "workshop", feature = "binary"))]
 	#[error("Steam error ({0})")]
 	/// Steam error during publishing
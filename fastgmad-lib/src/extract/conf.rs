@@ -1,3 +1,4 @@
+use super::{PathEncoding, PortableNames};
 use std::{num::NonZeroUsize, path::PathBuf};
 
 macro_rules! nonzero {
@@ -10,7 +11,9 @@ macro_rules! nonzero {
 }
 
 /// Options for .GMA extraction
-#[derive(Debug)]
+///
+/// Extraction can only be cancelled (with Ctrl-C) when the `binary` feature is enabled.
+#[derive(Debug, Clone)]
 pub struct ExtractGmaConfig {
 	/// The folder to extract the .GMA to
 	pub out: PathBuf,
@@ -21,8 +24,38 @@ pub struct ExtractGmaConfig {
 	/// The maximum amount of memory to use for I/O
 	pub max_io_memory_usage: NonZeroUsize,
 
+	/// Mirror the .GMA into the output folder, only rewriting files whose contents changed and deleting files no longer in the .GMA
+	///
+	/// Entries are processed serially in this mode.
+	pub sync: bool,
+
+	/// Extract into a staging directory next to the output folder and only move it into place once extraction succeeds
+	///
+	/// If the output folder already contains files, the extracted files are moved into it one by one, replacing files at the same paths;
+	/// anything else in it is left alone.
+	///
+	/// Ignored if `sync` is set.
+	pub atomic: bool,
+
+	/// How to decode the file paths stored in the .GMA
+	pub path_encoding: PathEncoding,
+
+	/// What to do with entries whose file names can't be created on every OS
+	pub portable_names: PortableNames,
+
+	/// Set the modification time of every extracted file to the timestamp stored in the .GMA
+	pub restore_mtime: bool,
+
+	/// Permissions to apply to every extracted file, e.g. `0o644`
+	#[cfg(unix)]
+	pub file_mode: Option<u32>,
+
 	#[cfg(feature = "binary")]
 	pub noprogress: bool,
+
+	/// The input contains several GMAs back to back, see [`super::extract_gma_stream`]
+	#[cfg(feature = "binary")]
+	pub multiple: bool,
 }
 impl ExtractGmaConfig {
 	#[cfg(feature = "binary")]
@@ -68,15 +101,60 @@ impl ExtractGmaConfig {
 							.ok_or(PrintHelp(Some("Expected a value after -folder")))?,
 					));
 				}
+				"-sync" => {
+					config.sync = true;
+				}
+				"-atomic" => {
+					config.atomic = true;
+				}
+				"-path-encoding" => {
+					config.path_encoding = args
+						.next()
+						.ok_or(PrintHelp(Some("Expected value for -path-encoding")))?
+						.to_str()
+						.and_then(|v| v.parse().ok())
+						.ok_or(PrintHelp(Some(
+							"Expected one of auto, utf8, cp1252, cpNNN, system-ansi or lossy for -path-encoding",
+						)))?;
+				}
+				"-portable-names" => {
+					config.portable_names = args
+						.next()
+						.ok_or(PrintHelp(Some("Expected value for -portable-names")))?
+						.to_str()
+						.and_then(|v| v.parse().ok())
+						.ok_or(PrintHelp(Some("Expected one of off, fail, skip or escape for -portable-names")))?;
+				}
+				"-restore-mtime" => {
+					config.restore_mtime = true;
+				}
+				#[cfg(unix)]
+				"-file-mode" => {
+					config.file_mode = Some(
+						args.next()
+							.ok_or(PrintHelp(Some("Expected value for -file-mode")))?
+							.to_str()
+							.and_then(|v| u32::from_str_radix(v, 8).ok())
+							.filter(|v| *v <= 0o7777)
+							.ok_or(PrintHelp(Some("Expected an octal file mode (e.g. 644) for -file-mode")))?,
+					);
+				}
 				"-noprogress" => {
 					config.noprogress = true;
 				}
+				"-multiple" => {
+					config.multiple = true;
+				}
 				_ => return Err(PrintHelp(Some("Unknown GMAD extraction argument"))),
 			}
 		}
 
 		let r#in = r#in.ok_or(PrintHelp(Some("Please provide an input path")))?;
 
+		if config.atomic && config.sync {
+			return Err(PrintHelp(Some("-atomic cannot be used with -sync")));
+		}
+
 		if config.out.as_os_str().is_empty() {
 			if let ExtractGmadIn::File(path) = &r#in {
 				let mut dir = path.to_owned();
@@ -98,9 +176,18 @@ impl Default for ExtractGmaConfig {
 			out: PathBuf::new(),
 			max_io_threads: std::thread::available_parallelism().unwrap_or_else(|_| nonzero!(NonZeroUsize::new(1))),
 			max_io_memory_usage: nonzero!(NonZeroUsize::new(2147483648)), // 2 GiB
+			sync: false,
+			atomic: false,
+			path_encoding: PathEncoding::default(),
+			portable_names: PortableNames::default(),
+			restore_mtime: false,
+			#[cfg(unix)]
+			file_mode: None,
 
 			#[cfg(feature = "binary")]
 			noprogress: false,
+			#[cfg(feature = "binary")]
+			multiple: false,
 		}
 	}
 }
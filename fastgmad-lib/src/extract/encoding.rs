@@ -0,0 +1,193 @@
+use std::ffi::OsString;
+
+/// How to decode the file paths stored in a .GMA
+///
+/// GMAs don't specify an encoding for their file paths; gmad.exe writes whatever bytes the system gave it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PathEncoding {
+	/// UTF-8, decoding paths that aren't valid UTF-8 as Windows-1252, which is what gmad.exe wrote on most Western systems
+	///
+	/// The paths that had to be decoded as Windows-1252 are listed once the extraction is done.
+	#[default]
+	Auto,
+
+	/// Strict UTF-8; entries with invalid UTF-8 paths are skipped
+	Utf8,
+
+	/// Windows-1252 (Western European), which every byte sequence can be decoded as
+	Cp1252,
+
+	/// A Windows code page, such as 1251 (Cyrillic) or 932 (Japanese); entries with paths that aren't valid in it are skipped
+	///
+	/// See [`PathEncoding::CODE_PAGES`] for the supported code pages.
+	CodePage(u16),
+
+	/// The behaviour of older versions of fastgmad: the system's ANSI code page on Windows, raw bytes on Unix
+	///
+	/// The same .GMA can produce different file names on different machines with this encoding.
+	SystemAnsi,
+
+	/// UTF-8, replacing invalid sequences with U+FFFD
+	Lossy,
+}
+impl std::str::FromStr for PathEncoding {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"auto" => Ok(Self::Auto),
+			"utf8" | "utf-8" => Ok(Self::Utf8),
+			"cp1252" | "windows-1252" => Ok(Self::Cp1252),
+			"system-ansi" => Ok(Self::SystemAnsi),
+			"lossy" => Ok(Self::Lossy),
+			_ => s
+				.strip_prefix("cp")
+				.or_else(|| s.strip_prefix("windows-"))
+				.and_then(|code_page| code_page.parse().ok())
+				.filter(|code_page| code_page_encoding(*code_page).is_some())
+				.map(Self::CodePage)
+				.ok_or(()),
+		}
+	}
+}
+impl std::fmt::Display for PathEncoding {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Auto => f.write_str("auto"),
+			Self::Utf8 => f.write_str("utf8"),
+			Self::Cp1252 => f.write_str("cp1252"),
+			Self::CodePage(code_page) => write!(f, "cp{code_page}"),
+			Self::SystemAnsi => f.write_str("system-ansi"),
+			Self::Lossy => f.write_str("lossy"),
+		}
+	}
+}
+impl PathEncoding {
+	/// The code pages [`PathEncoding::CodePage`] supports: every Windows ANSI code page, plus DOS Cyrillic, KOI8-R and UTF-8
+	pub const CODE_PAGES: &'static [u16] = &[
+		866, 874, 932, 936, 949, 950, 1250, 1251, 1252, 1253, 1254, 1255, 1256, 1257, 1258, 20866, 65001,
+	];
+
+	/// Decodes a file path from a .GMA, returning `None` if it can't be represented in this encoding
+	pub(crate) fn decode(self, path: &[u8]) -> Option<OsString> {
+		match self {
+			Self::Auto => Some(OsString::from(match std::str::from_utf8(path) {
+				Ok(path) => path.to_owned(),
+				Err(_) => decode_cp1252(path),
+			})),
+			Self::Utf8 => std::str::from_utf8(path).ok().map(OsString::from),
+			Self::Cp1252 => Some(OsString::from(decode_cp1252(path))),
+			Self::CodePage(code_page) => code_page_encoding(code_page)?
+				.decode_without_bom_handling_and_without_replacement(path)
+				.map(|path| OsString::from(path.into_owned())),
+			Self::Lossy => Some(OsString::from(String::from_utf8_lossy(path).into_owned())),
+			Self::SystemAnsi => {
+				#[cfg(unix)]
+				{
+					use std::os::unix::ffi::OsStringExt;
+					Some(OsString::from_vec(path.to_vec()))
+				}
+				#[cfg(windows)]
+				{
+					let code_page = unsafe { winapi::um::winnls::GetACP() };
+					Self::CodePage(u16::try_from(code_page).ok()?).decode(path)
+				}
+				#[cfg(not(any(unix, windows)))]
+				{
+					std::str::from_utf8(path).ok().map(OsString::from)
+				}
+			}
+		}
+	}
+
+	/// Whether [`PathEncoding::Auto`] has to fall back to Windows-1252 for this path
+	pub(crate) fn falls_back(self, path: &[u8]) -> bool {
+		self == Self::Auto && std::str::from_utf8(path).is_err()
+	}
+}
+
+fn code_page_encoding(code_page: u16) -> Option<&'static encoding_rs::Encoding> {
+	Some(match code_page {
+		866 => encoding_rs::IBM866,
+		874 => encoding_rs::WINDOWS_874,
+		932 => encoding_rs::SHIFT_JIS,
+		936 => encoding_rs::GBK,
+		949 => encoding_rs::EUC_KR,
+		950 => encoding_rs::BIG5,
+		1250 => encoding_rs::WINDOWS_1250,
+		1251 => encoding_rs::WINDOWS_1251,
+		1252 => encoding_rs::WINDOWS_1252,
+		1253 => encoding_rs::WINDOWS_1253,
+		1254 => encoding_rs::WINDOWS_1254,
+		1255 => encoding_rs::WINDOWS_1255,
+		1256 => encoding_rs::WINDOWS_1256,
+		1257 => encoding_rs::WINDOWS_1257,
+		1258 => encoding_rs::WINDOWS_1258,
+		20866 => encoding_rs::KOI8_R,
+		65001 => encoding_rs::UTF_8,
+		_ => return None,
+	})
+}
+
+// https://www.unicode.org/Public/MAPPINGS/VENDORS/MICSFT/WINDOWS/CP1252.TXT
+// 0x81, 0x8D, 0x8F, 0x90 and 0x9D are undefined and map to their C1 control characters, like MultiByteToWideChar does.
+const CP1252_HIGH: [char; 32] = [
+	'\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}', '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}',
+	'\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}', '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+	'\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+fn decode_cp1252(bytes: &[u8]) -> String {
+	bytes
+		.iter()
+		.map(|&byte| match byte {
+			0x80..=0x9F => CP1252_HIGH[(byte - 0x80) as usize],
+			// The rest of the code page is identical to Latin-1
+			_ => byte as char,
+		})
+		.collect()
+}
+
+#[test]
+fn test_path_encoding() {
+	assert_eq!(
+		PathEncoding::Utf8.decode("lua/привет.lua".as_bytes()),
+		Some(OsString::from("lua/привет.lua"))
+	);
+	assert_eq!(PathEncoding::Utf8.decode(b"lua/caf\xE9.lua"), None);
+
+	assert_eq!(PathEncoding::Cp1252.decode(b"lua/caf\xE9.lua"), Some(OsString::from("lua/café.lua")));
+	assert_eq!(PathEncoding::Cp1252.decode(b"lua/\x80\x9F.lua"), Some(OsString::from("lua/€Ÿ.lua")));
+
+	assert_eq!(
+		PathEncoding::Lossy.decode(b"lua/caf\xE9.lua"),
+		Some(OsString::from("lua/caf\u{FFFD}.lua"))
+	);
+
+	assert_eq!(PathEncoding::SystemAnsi.decode(b"lua/test.lua"), Some(OsString::from("lua/test.lua")));
+
+	assert_eq!(
+		PathEncoding::Auto.decode("lua/привет.lua".as_bytes()),
+		Some(OsString::from("lua/привет.lua"))
+	);
+	assert_eq!(PathEncoding::Auto.decode(b"lua/caf\xE9.lua"), Some(OsString::from("lua/café.lua")));
+	assert!(PathEncoding::Auto.falls_back(b"lua/caf\xE9.lua"));
+	assert!(!PathEncoding::Auto.falls_back("lua/привет.lua".as_bytes()));
+
+	assert_eq!("cp1251".parse(), Ok(PathEncoding::CodePage(1251)));
+	assert_eq!("windows-932".parse(), Ok(PathEncoding::CodePage(932)));
+	assert_eq!("cp1".parse::<PathEncoding>(), Err(()));
+	assert_eq!(
+		PathEncoding::CodePage(1251).decode(b"lua/\xEF\xF0\xE8\xE2\xE5\xF2.lua"),
+		Some(OsString::from("lua/привет.lua"))
+	);
+	assert_eq!(
+		PathEncoding::CodePage(932).decode(b"lua/\x82\xA0.lua"),
+		Some(OsString::from("lua/あ.lua"))
+	);
+	assert_eq!(PathEncoding::CodePage(932).decode(b"lua/\x82.lua"), None);
+
+	for code_page in PathEncoding::CODE_PAGES {
+		assert!(code_page_encoding(*code_page).is_some(), "{code_page}");
+	}
+}
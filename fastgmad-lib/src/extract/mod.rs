@@ -6,130 +6,339 @@ use crate::{
 use byteorder::{ReadBytesExt, LE};
 use std::{
 	borrow::Cow,
-	fs::File,
-	io::{BufRead, BufWriter, Read, Write},
+	collections::{BTreeMap, HashMap, HashSet},
+	ffi::OsString,
+	fs::{File, OpenOptions},
+	io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
 	path::{Component, Path, PathBuf},
-	sync::{atomic::AtomicUsize, Mutex},
+	sync::{
+		atomic::{AtomicBool, AtomicUsize},
+		Condvar, Mutex,
+	},
+	time::{Duration, SystemTime},
 };
+use uuid::Uuid;
 
 mod conf;
 pub use conf::ExtractGmaConfig;
 
+mod encoding;
+pub use encoding::PathEncoding;
+
+mod portable;
+pub use portable::PortableNames;
+
 #[cfg(feature = "binary")]
 pub use conf::ExtractGmadIn;
 
 /// Extracts a GMA file to a directory.
+///
+/// Prefer [`extract_gma_from_file`] if you're reading from a file, as it supports parallel reads.
 pub fn extract_gma(conf: &ExtractGmaConfig, r: &mut (impl BufRead + IoSkip)) -> Result<(), FastGmadError> {
-	if conf.max_io_threads.get() == 1 {
+	if conf.sync {
+		SyncExtractGma::extract_gma_with_done_callback(conf, r, &mut || ())
+	} else if conf.max_io_threads.get() == 1 {
 		StandardExtractGma::extract_gma_with_done_callback(conf, r, &mut || ())
 	} else {
 		ParallelExtractGma::extract_gma_with_done_callback(conf, r, &mut || ())
 	}
 }
 
+/// Extracts a GMA file to a directory.
+///
+/// Prefer this function over [`extract_gma`] if you're reading from a file, as this function reads entries from the file in parallel.
+pub fn extract_gma_from_file(conf: &ExtractGmaConfig, r: &mut BufReader<File>) -> Result<(), FastGmadError> {
+	if conf.sync {
+		SyncExtractGma::extract_gma_with_done_callback(conf, r, &mut || ())
+	} else if conf.max_io_threads.get() == 1 {
+		StandardExtractGma::extract_gma_with_done_callback(conf, r, &mut || ())
+	} else {
+		PreadExtractGma::extract_gma_with_done_callback(conf, r, &mut || ())
+	}
+}
+
+/// Extracts GMA files that were written back to back, such as several GMAs piped through stdin, to subdirectories of [`ExtractGmaConfig::out`].
+///
+/// Each GMA is extracted to a directory named after its title, or its position in the stream (starting from 1) if the title is empty or already taken.
+/// Returns the directories the GMAs were extracted to.
+pub fn extract_gma_stream(conf: &ExtractGmaConfig, r: &mut (impl BufRead + IoSkip)) -> Result<Vec<PathBuf>, FastGmadError> {
+	if conf.sync {
+		extract_gma_stream_with::<_, SyncExtractGma>(conf, r)
+	} else if conf.max_io_threads.get() == 1 {
+		extract_gma_stream_with::<_, StandardExtractGma>(conf, r)
+	} else {
+		extract_gma_stream_with::<_, ParallelExtractGma>(conf, r)
+	}
+}
+
+/// Extracts a memory-mapped GMA file to a directory.
+///
+/// Entries are written straight from the mapping, and the file is checked for modifications before this returns.
+#[cfg(feature = "mmap")]
+pub fn extract_mmap_gma(conf: &ExtractGmaConfig, gma: &crate::mmap::MmapGma) -> Result<(), FastGmadError> {
+	let mut r = gma.as_bytes();
+	if conf.sync {
+		SyncExtractGma::extract_gma_with_done_callback(conf, &mut r, &mut || ())?;
+	} else {
+		MmapExtractGma::extract_gma_with_done_callback(conf, &mut r, &mut || ())?;
+	}
+	gma.verify()
+}
+
 #[cfg(feature = "binary")]
 pub fn extract_gma_with_done_callback(
 	conf: &ExtractGmaConfig,
 	r: &mut (impl BufRead + IoSkip),
 	done_callback: &mut dyn FnMut(),
 ) -> Result<(), FastGmadError> {
-	if conf.max_io_threads.get() == 1 {
-		StandardExtractGma::extract_gma_with_done_callback(conf, r, done_callback)
+	let _ctrlc_handle = crate::ctrlc_handling::CtrlCHandle::get();
+	clean_up_if_aborted(conf, |conf| {
+		if conf.sync {
+			SyncExtractGma::extract_gma_with_done_callback(conf, r, done_callback)
+		} else if conf.max_io_threads.get() == 1 {
+			StandardExtractGma::extract_gma_with_done_callback(conf, r, done_callback)
+		} else {
+			ParallelExtractGma::extract_gma_with_done_callback(conf, r, done_callback)
+		}
+	})
+}
+
+#[cfg(feature = "binary")]
+pub fn extract_gma_from_file_with_done_callback(
+	conf: &ExtractGmaConfig,
+	r: &mut BufReader<File>,
+	done_callback: &mut dyn FnMut(),
+) -> Result<(), FastGmadError> {
+	let _ctrlc_handle = crate::ctrlc_handling::CtrlCHandle::get();
+	clean_up_if_aborted(conf, |conf| {
+		if conf.sync {
+			SyncExtractGma::extract_gma_with_done_callback(conf, r, done_callback)
+		} else if conf.max_io_threads.get() == 1 {
+			StandardExtractGma::extract_gma_with_done_callback(conf, r, done_callback)
+		} else {
+			PreadExtractGma::extract_gma_with_done_callback(conf, r, done_callback)
+		}
+	})
+}
+
+#[cfg(feature = "binary")]
+pub fn extract_gma_stream_with_done_callback(
+	conf: &ExtractGmaConfig,
+	r: &mut (impl BufRead + IoSkip),
+	done_callback: &mut dyn FnMut(),
+) -> Result<Vec<PathBuf>, FastGmadError> {
+	let _ctrlc_handle = crate::ctrlc_handling::CtrlCHandle::get();
+	let outs = clean_up_if_aborted(conf, |conf| {
+		if conf.sync {
+			extract_gma_stream_with::<_, SyncExtractGma>(conf, r)
+		} else if conf.max_io_threads.get() == 1 {
+			extract_gma_stream_with::<_, StandardExtractGma>(conf, r)
+		} else {
+			extract_gma_stream_with::<_, ParallelExtractGma>(conf, r)
+		}
+	})?;
+	done_callback();
+	Ok(outs)
+}
+
+fn extract_gma_stream_with<R: BufRead + IoSkip, E: ExtractGma<R>>(conf: &ExtractGmaConfig, r: &mut R) -> Result<Vec<PathBuf>, FastGmadError> {
+	let mut outs = Vec::new();
+	let mut taken_names = HashSet::new();
+	loop {
+		// Each GMA ends right after its last entry, so we've reached the end of the stream if there's nothing left to read
+		if r.fill_buf()
+			.map_err(|error| fastgmad_io_error!(while "reading GMA stream", error: error))?
+			.is_empty()
+		{
+			break;
+		}
+
+		let num = outs.len() + 1;
+		log::info!("Reading metadata of GMA #{num}...");
+		let header = GmaHeader::read(r)?;
+
+		let conf = ExtractGmaConfig {
+			out: conf.out.join(stream_out_dir_name(&header.title, num, &mut taken_names)),
+			..conf.clone()
+		};
+		log::info!("Extracting GMA #{num} to {}...", conf.out.display());
+		E::extract_gma_contents(&conf, header, r, &mut || ())?;
+
+		outs.push(conf.out);
+	}
+	Ok(outs)
+}
+
+/// Names the output directory of a GMA in a stream after its title, falling back to its position in the stream
+fn stream_out_dir_name(title: &[u8], num: usize, taken_names: &mut HashSet<String>) -> String {
+	let title = String::from_utf8_lossy(title).replace('/', "_");
+	let title = portable::escape(title.trim()).into_owned();
+
+	// Compare case-insensitively, in case we're on Windows or macOS
+	let mut name = if matches!(title.as_str(), "" | "." | "..") || taken_names.contains(&title.to_lowercase()) {
+		num.to_string()
 	} else {
-		ParallelExtractGma::extract_gma_with_done_callback(conf, r, done_callback)
+		title
+	};
+	while taken_names.contains(&name.to_lowercase()) {
+		name.push('_');
 	}
+
+	taken_names.insert(name.to_lowercase());
+	name
 }
 
-trait ExtractGma {
-	fn extract_gma_with_done_callback(
-		conf: &ExtractGmaConfig,
-		r: &mut (impl BufRead + IoSkip),
-		done_callback: &mut dyn FnMut(),
-	) -> Result<(), FastGmadError> {
-		if conf.out.is_dir() {
-			log::warn!(
-				"Output directory already exists; files not present in this GMA but present in the existing output directory will NOT be deleted"
-			);
+#[cfg(feature = "binary")]
+fn clean_up_if_aborted<T>(conf: &ExtractGmaConfig, extract: impl FnOnce(&ExtractGmaConfig) -> Result<T, FastGmadError>) -> Result<T, FastGmadError> {
+	let created_out = !conf.out.exists();
+
+	let res = extract(conf);
+
+	if let Err(FastGmadError {
+		kind: crate::error::FastGmadErrorKind::Aborted,
+		..
+	}) = &res
+	{
+		// The staging directory cleans up after itself in atomic mode
+		if created_out && !conf.atomic {
+			std::fs::remove_dir_all(&conf.out).ok();
+		} else if !conf.atomic {
+			log::warn!("Extraction was aborted; the output directory may contain a partial extraction");
 		}
+	}
 
-		std::fs::create_dir_all(&conf.out).map_err(|error| fastgmad_io_error!(while "creating output directory", error: error, path: conf.out))?;
+	res
+}
 
+trait ExtractGma<R: BufRead + IoSkip> {
+	fn extract_gma_with_done_callback(conf: &ExtractGmaConfig, r: &mut R, done_callback: &mut dyn FnMut()) -> Result<(), FastGmadError> {
 		log::info!("Reading metadata...");
+		let header = GmaHeader::read(r)?;
+		Self::extract_gma_contents(conf, header, r, done_callback)
+	}
+
+	/// Extracts the rest of a GMA whose header has already been read
+	fn extract_gma_contents(conf: &ExtractGmaConfig, header: GmaHeader, r: &mut R, done_callback: &mut dyn FnMut()) -> Result<(), FastGmadError> {
+		let GmaHeader {
+			timestamp,
+			title,
+			addon_json,
+		} = header;
+
+		// File index
+		// Read it before creating anything, so that nothing is written if an entry makes the extraction fail
+		log::info!("Reading file list...");
 
 		let mut buf = Vec::new();
 
+		let mut index = Vec::new();
+		let mut undecodable = Vec::new();
+		let mut fell_back = Vec::new();
+		let mut escaped_names = BTreeMap::new();
+		let mut lowercase_paths = HashMap::new();
+		let mut extracted_names = HashMap::new();
+		while r
+			.read_u32::<LE>()
+			.map_err(|error| fastgmad_io_error!(while "reading entry index", error: error))?
+			!= 0
 		{
-			let mut magic = [0u8; 4];
-			let res = r.read_exact(&mut magic);
-			if let Err(error) = res {
-				if error.kind() != std::io::ErrorKind::UnexpectedEof {
-					return Err(fastgmad_io_error!(while "reading GMA magic bytes", error: error));
-				}
-			}
-			if magic != GMA_MAGIC {
-				return Err(fastgmad_io_error!(error: std::io::Error::new(std::io::ErrorKind::InvalidData, "File is not in GMA format")));
-			}
-		}
+			let path = {
+				buf.clear();
+				let path = r
+					.read_nul_str(&mut buf)
+					.map_err(|error| fastgmad_io_error!(while "reading entry path", error: error))?;
+				path.to_vec()
+			};
 
-		let version = r
-			.read_u8()
-			.map_err(|error| fastgmad_io_error!(while "reading version byte", error: error))?;
-		if version != GMA_VERSION {
-			log::warn!("File is in GMA version {version}, expected version {GMA_VERSION}, reading anyway...");
-		}
+			let size = r
+				.read_i64::<LE>()
+				.map_err(|error| fastgmad_io_error!(while "reading entry size", error: error))?;
 
-		// SteamID (unused)
-		r.read_exact(&mut [0u8; 8])
-			.map_err(|error| fastgmad_io_error!(while "reading SteamID", error: error))?;
+			let _crc = r
+				.read_u32::<LE>()
+				.map_err(|error| fastgmad_io_error!(while "reading entry CRC", error: error))?;
 
-		// Timestamp
-		r.read_exact(&mut [0u8; 8])
-			.map_err(|error| fastgmad_io_error!(while "reading timestamp", error: error))?;
+			let mut decoded_path = conf.path_encoding.decode(&path);
+			if decoded_path.is_none() {
+				undecodable.push(path);
+			} else if conf.path_encoding.falls_back(&path) {
+				fell_back.push(path);
+			}
 
-		if version > 1 {
-			// Required content
-			loop {
-				buf.clear();
+			let mut original_name = None;
+
+			if conf.portable_names != PortableNames::Off {
+				if let Some(name) = decoded_path.as_ref().map(|path| path.to_string_lossy().into_owned()) {
+					if !portable::is_portable(&name) {
+						match conf.portable_names {
+							PortableNames::Off => unreachable!(),
+							PortableNames::Fail => return Err(fastgmad_error!(error: NonPortableFileName(name))),
+							PortableNames::Skip => {
+								log::warn!("Skipping GMA entry with non-portable file name: {name}");
+								decoded_path = None;
+							}
+							PortableNames::Escape => {
+								let escaped = portable::escape(&name).into_owned();
+								decoded_path = Some(OsString::from(&escaped));
+								escaped_names.insert(escaped, name.clone());
+								original_name = Some(name);
+							}
+						}
+					}
+				}
+			}
 
-				let content = r
-					.read_nul_str(&mut buf)
-					.map_err(|error| fastgmad_io_error!(while "reading required content", error: error))?;
+			if let Some(decoded_path) = &decoded_path {
+				let decoded_path = decoded_path.to_string_lossy();
+				if let Some(other) = lowercase_paths.insert(decoded_path.to_lowercase(), decoded_path.clone().into_owned()) {
+					log::warn!(
+						"GMA entries {other} and {decoded_path} only differ by case; on a case-insensitive file system (Windows, macOS) one will overwrite the other"
+					);
+				}
+			}
 
-				if content.is_empty() {
-					break;
+			if conf.portable_names == PortableNames::Escape {
+				// An escaped name must not be the same as another entry's name, or one would overwrite the other
+				if let Some(decoded_path) = &decoded_path {
+					let decoded_path = decoded_path.to_string_lossy().into_owned();
+					let original = original_name.unwrap_or_else(|| decoded_path.clone());
+					if let Some(other) = extracted_names.insert(decoded_path.clone(), original.clone()) {
+						if other != original {
+							return Err(fastgmad_error!(error: EscapedNameCollision(other, original, decoded_path)));
+						}
+					}
 				}
 			}
-		}
 
-		// Addon name
-		let title = {
-			buf.clear();
+			index.push((decoded_path, size));
+		}
 
-			let title = r
-				.read_nul_str(&mut buf)
-				.map_err(|error| fastgmad_io_error!(while "reading addon name", error: error))?;
+		if conf.out.is_dir() && !conf.sync {
+			log::warn!(
+				"Output directory already exists; files not present in this GMA but present in the existing output directory will NOT be deleted"
+			);
+		}
 
-			title.to_vec()
+		// In atomic mode, extract into a staging directory and move it into place at the end
+		let staging = if conf.atomic && !conf.sync {
+			Some(StagingDir::new(&conf.out)?)
+		} else {
+			None
 		};
-
-		// addon.json
-		let addon_json = {
-			buf.clear();
-
-			let addon_json = r
-				.read_nul_str(&mut buf)
-				.map_err(|error| fastgmad_io_error!(while "reading addon description", error: error))?;
-
-			addon_json.to_vec()
+		let out = &conf.out;
+		let staging_conf;
+		let conf = match &staging {
+			Some(staging) => {
+				staging_conf = ExtractGmaConfig {
+					out: staging.0.clone(),
+					..conf.clone()
+				};
+				&staging_conf
+			}
+			None => conf,
 		};
 
-		// Addon author
-		r.skip_nul_str()
-			.map_err(|error| fastgmad_io_error!(while "reading addon author", error: error))?;
-
-		// Addon version (unused)
-		r.read_exact(&mut [0u8; 4])
-			.map_err(|error| fastgmad_io_error!(while "reading addon version", error: error))?;
+		std::fs::create_dir_all(&conf.out).map_err(|error| fastgmad_io_error!(while "creating output directory", error: error, path: conf.out))?;
 
 		log::info!("Writing addon.json...");
 		let addon_json_path;
@@ -166,43 +375,13 @@ trait ExtractGma {
 				.map_err(|error| fastgmad_io_error!(while "flushing addon.json", error: error, path: addon_json_path))?;
 		}
 
-		// File index
-		log::info!("Reading file list...");
+		let file_index = index
+			.into_iter()
+			.map(|(path, size)| GmaEntry::new(&conf.out, path, size))
+			.collect::<Result<Vec<_>, _>>()?;
 
 		#[cfg(feature = "binary")]
-		let mut total_size = 0;
-
-		let mut file_index = Vec::new();
-		while r
-			.read_u32::<LE>()
-			.map_err(|error| fastgmad_io_error!(while "reading entry index", error: error))?
-			!= 0
-		{
-			let path = {
-				buf.clear();
-				let path = r
-					.read_nul_str(&mut buf)
-					.map_err(|error| fastgmad_io_error!(while "reading entry path", error: error))?;
-				path.to_vec()
-			};
-
-			let size = r
-				.read_i64::<LE>()
-				.map_err(|error| fastgmad_io_error!(while "reading entry size", error: error))?;
-
-			let _crc = r
-				.read_u32::<LE>()
-				.map_err(|error| fastgmad_io_error!(while "reading entry CRC", error: error))?;
-
-			let entry = GmaEntry::new(&conf.out, path, size)?;
-
-			#[cfg(feature = "binary")]
-			{
-				total_size += entry.size as u64;
-			}
-
-			file_index.push(entry);
-		}
+		let total_size = file_index.iter().map(|entry| entry.size as u64).sum();
 
 		// File contents
 		log::info!("Extracting entries...");
@@ -215,6 +394,88 @@ trait ExtractGma {
 			&file_index,
 		)?;
 
+		if !fell_back.is_empty() {
+			log::warn!(
+				"Decoded {} GMA entry paths that aren't valid UTF-8 as Windows-1252 (use -path-encoding to pick another encoding):",
+				fell_back.len()
+			);
+			for path in fell_back.iter() {
+				log::warn!("    {}", String::from_utf8_lossy(path));
+			}
+		}
+
+		if !undecodable.is_empty() {
+			log::warn!(
+				"Skipped {} GMA entries whose file paths could not be decoded as {}:",
+				undecodable.len(),
+				conf.path_encoding
+			);
+			for path in undecodable.iter() {
+				log::warn!("    {}", String::from_utf8_lossy(path));
+			}
+		}
+
+		if conf.portable_names == PortableNames::Escape {
+			// Record the original names so that they can be restored when the GMA is recreated
+			let escaped_names_path = conf.out.join(crate::ESCAPED_NAMES_FILE);
+			if !escaped_names.is_empty() {
+				log::warn!(
+					"Escaped {} non-portable file names, the original names are recorded in {}",
+					escaped_names.len(),
+					crate::ESCAPED_NAMES_FILE
+				);
+
+				let json = serde_json::to_vec_pretty(&escaped_names)
+					.map_err(|error| fastgmad_error!(while "serializing escaped file names", error: error))?;
+				std::fs::write(&escaped_names_path, json)
+					.map_err(|error| fastgmad_io_error!(while "writing escaped file names", error: error, path: escaped_names_path))?;
+			} else if escaped_names_path.is_file() {
+				std::fs::remove_file(&escaped_names_path)
+					.map_err(|error| fastgmad_io_error!(while "deleting stale escaped file names", error: error, path: escaped_names_path))?;
+			}
+		}
+
+		#[cfg(unix)]
+		let file_mode = conf.file_mode;
+		#[cfg(not(unix))]
+		let file_mode = None::<u32>;
+
+		if conf.restore_mtime || file_mode.is_some() {
+			log::info!("Applying file metadata...");
+
+			let mtime = SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(timestamp));
+			if conf.restore_mtime && mtime.is_none() {
+				log::warn!("GMA timestamp {timestamp} is out of range; not restoring modification times");
+			}
+
+			for path in file_index.iter().filter_map(|entry| entry.path.as_deref()) {
+				if !path.is_file() {
+					// We skipped this entry
+					continue;
+				}
+
+				if let Some(mtime) = mtime.filter(|_| conf.restore_mtime) {
+					File::options()
+						.write(true)
+						.open(path)
+						.and_then(|f| f.set_modified(mtime))
+						.map_err(|error| fastgmad_io_error!(while "setting GMA entry modification time", error: error, path: path))?;
+				}
+
+				#[cfg(unix)]
+				if let Some(file_mode) = file_mode {
+					use std::os::unix::fs::PermissionsExt;
+					std::fs::set_permissions(path, std::fs::Permissions::from_mode(file_mode))
+						.map_err(|error| fastgmad_io_error!(while "setting GMA entry permissions", error: error, path: path))?;
+				}
+			}
+		}
+
+		if let Some(staging) = staging {
+			log::info!("Moving extracted files into place...");
+			staging.commit(out)?;
+		}
+
 		// Explicitly free memory here
 		// We may exit the process in done_callback (thereby allowing the OS to free the memory),
 		// so make sure the optimiser knows to free all the memory here.
@@ -229,17 +490,17 @@ trait ExtractGma {
 
 	fn write_entries(
 		conf: &ExtractGmaConfig,
-		r: &mut (impl BufRead + IoSkip),
+		r: &mut R,
 		#[cfg(feature = "binary")] total_size: u64,
 		file_index: &[GmaEntry],
 	) -> Result<(), FastGmadError>;
 }
 
 struct StandardExtractGma;
-impl ExtractGma for StandardExtractGma {
+impl<R: BufRead + IoSkip> ExtractGma<R> for StandardExtractGma {
 	fn write_entries(
 		conf: &ExtractGmaConfig,
-		mut r: &mut (impl BufRead + IoSkip),
+		r: &mut R,
 		#[cfg(feature = "binary")] total_size: u64,
 		file_index: &[GmaEntry],
 	) -> Result<(), FastGmadError> {
@@ -251,6 +512,11 @@ impl ExtractGma for StandardExtractGma {
 		};
 
 		for GmaEntry { path, size } in file_index.iter() {
+			#[cfg(feature = "binary")]
+			if crate::ctrlc_handling::cancelled() {
+				return Err(fastgmad_error!(error: Aborted));
+			}
+
 			let path = match path {
 				Some(path) => path,
 				None => {
@@ -284,13 +550,16 @@ impl ExtractGma for StandardExtractGma {
 				}
 			};
 
-			let mut take = r.take(*size as u64);
-			std::io::copy(&mut take, &mut w).map_err(|error| fastgmad_io_error!(while "copying GMA entry data", error: error, path: path))?;
-
-			w.flush()
-				.map_err(|error| fastgmad_io_error!(while "flushing GMA entry file", error: error, path: path))?;
-
-			r = take.into_inner();
+			// Copied inside the kernel if `r` is a file
+			let res = r
+				.copy_to_file(&w, *size as u64)
+				.and_then(|copied| check_entry_len(copied, *size))
+				.map_err(|error| fastgmad_io_error!(while "copying GMA entry data", error: error, path: path))
+				.and_then(|_| {
+					w.flush()
+						.map_err(|error| fastgmad_io_error!(while "flushing GMA entry file", error: error, path: path))
+				});
+			remove_if_failed(w, path, res)?;
 
 			#[cfg(feature = "binary")]
 			if let Some(progress) = &mut progress {
@@ -303,10 +572,10 @@ impl ExtractGma for StandardExtractGma {
 }
 
 struct ParallelExtractGma;
-impl ExtractGma for ParallelExtractGma {
+impl<R: BufRead + IoSkip> ExtractGma<R> for ParallelExtractGma {
 	fn write_entries(
 		conf: &ExtractGmaConfig,
-		r: &mut (impl BufRead + IoSkip),
+		r: &mut R,
 		#[cfg(feature = "binary")] total_size: u64,
 		file_index: &[GmaEntry],
 	) -> Result<(), FastGmadError> {
@@ -317,17 +586,84 @@ impl ExtractGma for ParallelExtractGma {
 			None
 		};
 
-		let memory_used = AtomicUsize::new(0);
-		let error = Mutex::new(None);
-		std::thread::scope(|s| {
-			let mut r = r;
+		// Entries are read serially by this thread, then handed off to a pool of I/O threads to be written
+		struct WriteQueue<'a> {
+			jobs: crossbeam_channel::Receiver<(&'a Path, Vec<u8>)>,
+			memory_usage: Mutex<usize>,
+			memory_usage_cvar: Condvar,
+			error: Mutex<Option<FastGmadError>>,
+		}
+		impl WriteQueue<'_> {
+			fn failed(&self) -> bool {
+				self.error.lock().unwrap().is_some()
+			}
+
+			fn release_memory(&self, size: usize) {
+				*self.memory_usage.lock().unwrap() -= size;
+				self.memory_usage_cvar.notify_all();
+			}
+		}
+
+		let (tx, rx) = crossbeam_channel::unbounded();
+		let queue = WriteQueue {
+			jobs: rx,
+			memory_usage: Mutex::new(0),
+			memory_usage_cvar: Condvar::new(),
+			error: Mutex::new(None),
+		};
+
+		std::thread::scope(|scope| {
+			// Moved in here so that the I/O threads are released if we bail out early
+			let tx = tx;
+
+			let buffered_entries = file_index
+				.iter()
+				.filter(|entry| entry.path.is_some() && entry.size <= conf.max_io_memory_usage.get())
+				.count();
+
+			for _ in 0..buffered_entries.min(conf.max_io_threads.get()) {
+				let queue = &queue;
+				scope.spawn(move || loop {
+					let job = queue.jobs.recv();
+					let (path, buf) = match job {
+						Ok(job) => job,
+						Err(_) => break,
+					};
+
+					let size = buf.len();
+
+					// Once something has gone wrong, just drain the queue
+					if !queue.failed() {
+						let res = (|| {
+							if let Some(parent) = path.parent() {
+								if parent != conf.out {
+									std::fs::create_dir_all(parent)
+										.map_err(|error| fastgmad_io_error!(while "creating directory for GMA entry", error: error, path: parent))?;
+								}
+							}
+
+							std::fs::write(path, buf).map_err(|error| fastgmad_io_error!(while "writing GMA entry file", error: error, path: path))
+						})();
+
+						if let Err(err) = res {
+							queue.error.lock().unwrap().get_or_insert(err);
+						}
+					}
+
+					queue.release_memory(size);
+				});
+			}
 
+			let mut r = r;
 			for GmaEntry { path, size } in file_index.iter() {
 				// Break early if an error occurs
-				match error.try_lock().as_deref() {
-					Ok(None) => {}
-					Ok(Some(_)) | Err(std::sync::TryLockError::WouldBlock) => break,
-					Err(err @ std::sync::TryLockError::Poisoned(_)) => Err(err).unwrap(),
+				if queue.failed() {
+					break;
+				}
+
+				#[cfg(feature = "binary")]
+				if crate::ctrlc_handling::cancelled() {
+					return Err(fastgmad_error!(error: Aborted));
 				}
 
 				let path = match path {
@@ -340,52 +676,34 @@ impl ExtractGma for ParallelExtractGma {
 					}
 				};
 
-				let can_buffer = |memory_used| {
-					let new_memory_used = memory_used + *size;
-					if new_memory_used <= conf.max_io_memory_usage.get() {
-						Some(new_memory_used)
-					} else {
-						None
-					}
-				};
+				if *size <= conf.max_io_memory_usage.get() {
+					// Wait until there's enough memory available to buffer this entry
+					let mut memory_usage = queue
+						.memory_usage_cvar
+						.wait_while(queue.memory_usage.lock().unwrap(), |memory_usage| {
+							*memory_usage > 0 && *memory_usage + *size > conf.max_io_memory_usage.get()
+						})
+						.unwrap();
+					*memory_usage += *size;
+					drop(memory_usage);
 
-				if *size <= conf.max_io_memory_usage.get()
-					&& memory_used
-						.fetch_update(std::sync::atomic::Ordering::SeqCst, std::sync::atomic::Ordering::SeqCst, can_buffer)
-						.is_ok()
-				{
 					let mut buf = Vec::with_capacity(*size);
 
 					let mut take = r.take(*size as u64);
-					take.read_to_end(&mut buf)
-						.map_err(|error| fastgmad_io_error!(while "reading GMA entry data", error: error, path: path))?;
+					let res = take
+						.read_to_end(&mut buf)
+						.and_then(|read| check_entry_len(read as u64, *size))
+						.map_err(|error| fastgmad_io_error!(while "reading GMA entry data", error: error, path: path));
 					r = take.into_inner();
 
-					let memory_used = &memory_used;
-					let error = &error;
-					s.spawn(move || {
-						let res = (move || {
-							if let Some(parent) = path.parent() {
-								if parent != conf.out {
-									std::fs::create_dir_all(parent)
-										.map_err(|error| fastgmad_io_error!(while "creating directory for GMA entry", error: error, path: parent))?;
-								}
-							}
-
-							std::fs::write(path, buf)
-								.map_err(|error| fastgmad_io_error!(while "writing GMA entry file", error: error, path: path))?;
-
-							Ok::<_, FastGmadError>(())
-						})();
-
-						memory_used.fetch_sub(*size, std::sync::atomic::Ordering::SeqCst);
+					if let Err(err) = res {
+						queue.release_memory(*size);
+						return Err(err);
+					}
 
-						if let Err(err) = res {
-							*error.lock().unwrap() = Some(err);
-						}
-					});
+					tx.send((path.as_path(), buf)).ok();
 				} else {
-					// Just do it without buffering
+					// Too big to buffer, just do it without buffering
 					if let Some(parent) = path.parent() {
 						if parent != conf.out {
 							std::fs::create_dir_all(parent)
@@ -396,9 +714,11 @@ impl ExtractGma for ParallelExtractGma {
 					let mut take = r.take(*size as u64);
 					let mut w =
 						File::create(path).map_err(|error| fastgmad_io_error!(while "creating file for GMA entry", error: error, path: path))?;
-					std::io::copy(&mut take, &mut w).map_err(|error| fastgmad_io_error!(while "copying GMA entry data", error: error, path: path))?;
-					w.flush()
-						.map_err(|error| fastgmad_io_error!(while "flushing GMA entry file", error: error, path: path))?;
+					let res = std::io::copy(&mut take, &mut w)
+						.and_then(|copied| check_entry_len(copied, *size))
+						.and_then(|_| w.flush())
+						.map_err(|error| fastgmad_io_error!(while "copying GMA entry data", error: error, path: path));
+					remove_if_failed(w, path, res)?;
 					r = take.into_inner();
 				}
 
@@ -408,9 +728,12 @@ impl ExtractGma for ParallelExtractGma {
 				}
 			}
 
+			// Let the I/O threads finish writing whatever is left in the queue
+			drop(tx);
+
 			Ok::<_, FastGmadError>(())
 		})?;
-		if let Some(err) = error.into_inner().unwrap() {
+		if let Some(err) = queue.error.into_inner().unwrap() {
 			return Err(err);
 		}
 
@@ -418,54 +741,634 @@ impl ExtractGma for ParallelExtractGma {
 	}
 }
 
-#[derive(serde::Serialize)]
-struct StubAddonJson<'a> {
-	title: Cow<'a, str>,
-	description: Cow<'a, str>,
+struct PreadExtractGma;
+impl PreadExtractGma {
+	#[allow(clippy::too_many_arguments)]
+	fn write_entry(
+		conf: &ExtractGmaConfig,
+		f: &File,
+		buf: &mut Vec<u8>,
+		chunk_size: usize,
+		path: &Path,
+		offset: u64,
+		size: usize,
+		stopped: &AtomicBool,
+	) -> Result<(), FastGmadError> {
+		if let Some(parent) = path.parent() {
+			if parent != conf.out {
+				std::fs::create_dir_all(parent)
+					.map_err(|error| fastgmad_io_error!(while "creating directory for GMA entry", error: error, path: parent))?;
+			}
+		}
+
+		let mut w = File::create(path).map_err(|error| fastgmad_io_error!(while "creating file for GMA entry", error: error, path: path))?;
+
+		let res = (|| {
+			// On Linux, try to copy the entry inside the kernel first
+			#[cfg(target_os = "linux")]
+			let mut kernel_copy = true;
+
+			// Entries bigger than the chunk size are streamed through the buffer a chunk at a time
+			let mut copied = 0;
+			while copied < size {
+				#[cfg(feature = "binary")]
+				if crate::ctrlc_handling::cancelled() {
+					return Err(fastgmad_error!(error: Aborted));
+				}
+
+				if stopped.load(std::sync::atomic::Ordering::SeqCst) {
+					return Err(fastgmad_io_error!(
+						while "copying GMA entry data",
+						error: std::io::Error::new(std::io::ErrorKind::Interrupted, "extraction was stopped"),
+						path: path
+					));
+				}
+
+				#[cfg(target_os = "linux")]
+				if kernel_copy {
+					// Doesn't use any memory, so use bigger chunks
+					const KERNEL_COPY_CHUNK_SIZE: usize = 64 * 1024 * 1024;
+
+					let chunk = (size - copied).min(KERNEL_COPY_CHUNK_SIZE);
+					let kernel_copied = crate::util::kernel_copy(f, offset + copied as u64, &w, chunk as u64)
+						.map_err(|error| fastgmad_io_error!(while "copying GMA entry data", error: error, path: path))?;
+
+					copied += kernel_copied as usize;
+					if kernel_copied < chunk as u64 {
+						// Copy the rest through userspace
+						kernel_copy = false;
+					}
+					continue;
+				}
+
+				let chunk = (size - copied).min(chunk_size);
+				if buf.len() < chunk {
+					buf.resize(chunk, 0);
+				}
+				let buf = &mut buf[..chunk];
+
+				crate::util::read_exact_at(f, buf, offset + copied as u64)
+					.map_err(|error| fastgmad_io_error!(while "reading GMA entry data", error: error, path: path))?;
+				w.write_all(buf)
+					.map_err(|error| fastgmad_io_error!(while "writing GMA entry file", error: error, path: path))?;
+
+				copied += chunk;
+			}
+
+			Ok(())
+		})();
+
+		remove_if_failed(w, path, res)
+	}
 }
+impl ExtractGma<BufReader<File>> for PreadExtractGma {
+	fn write_entries(
+		conf: &ExtractGmaConfig,
+		r: &mut BufReader<File>,
+		#[cfg(feature = "binary")] total_size: u64,
+		file_index: &[GmaEntry],
+	) -> Result<(), FastGmadError> {
+		// We know where every entry is in the file now that we've read the index,
+		// so the I/O threads can read their entries straight from the file instead of going through this thread
+		let contents_ptr = r
+			.stream_position()
+			.map_err(|error| fastgmad_io_error!(while "getting stream position", error: error))?;
 
-struct GmaEntry {
-	path: Option<PathBuf>,
-	size: usize,
+		let (entries, contents_len) = entry_offsets(file_index);
+
+		// Split the memory budget between the I/O threads
+		let threads = entries.len().min(conf.max_io_threads.get());
+		let chunk_size = (conf.max_io_memory_usage.get() / threads.max(1)).max(1);
+
+		let f = r.get_ref();
+		write_entries_on_pool(
+			conf,
+			#[cfg(feature = "binary")]
+			total_size,
+			&entries,
+			|buf, path, offset, size, stopped| Self::write_entry(conf, f, buf, chunk_size, path, contents_ptr + offset, size, stopped),
+		)?;
+
+		// Leave the reader at the end of the GMA, as if we had read the entries through it
+		r.seek(SeekFrom::Start(contents_ptr + contents_len))
+			.map_err(|error| fastgmad_io_error!(while "seeking past GMA entry data", error: error))?;
+
+		Ok(())
+	}
 }
-impl GmaEntry {
-	fn new(base_path: &Path, path: Vec<u8>, size: i64) -> Result<Self, FastGmadError> {
-		let path = {
-			#[cfg(unix)]
-			{
-				use std::{ffi::OsString, os::unix::ffi::OsStringExt};
-				let path = OsString::from_vec(path);
-				Some(path)
-			}
-			#[cfg(windows)]
-			{
-				use std::{ffi::OsString, os::windows::ffi::OsStringExt};
-				match crate::util::ansi_to_wide(&path) {
-					Ok(path) => Some(OsString::from_wide(&path)),
-					Err(err) => {
-						log::info!(
-							"warning: skipping GMA entry with incompatible file path: {:?} ({err})",
-							String::from_utf8_lossy(&path),
-						);
-						None
+
+#[cfg(feature = "mmap")]
+struct MmapExtractGma;
+#[cfg(feature = "mmap")]
+impl<'a> ExtractGma<&'a [u8]> for MmapExtractGma {
+	fn write_entries(
+		conf: &ExtractGmaConfig,
+		r: &mut &'a [u8],
+		#[cfg(feature = "binary")] total_size: u64,
+		file_index: &[GmaEntry],
+	) -> Result<(), FastGmadError> {
+		let contents = *r;
+		let (entries, contents_len) = entry_offsets(file_index);
+		r.skip(contents_len).map_err(|_| {
+			fastgmad_io_error!(
+				while "reading GMA entry data",
+				error: std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "GMA entry data is truncated")
+			)
+		})?;
+
+		write_entries_on_pool(
+			conf,
+			#[cfg(feature = "binary")]
+			total_size,
+			&entries,
+			|_, path, offset, size, _| {
+				if let Some(parent) = path.parent() {
+					if parent != conf.out {
+						std::fs::create_dir_all(parent)
+							.map_err(|error| fastgmad_io_error!(while "creating directory for GMA entry", error: error, path: parent))?;
 					}
 				}
+
+				let offset = offset as usize;
+				std::fs::write(path, &contents[offset..offset + size])
+					.map_err(|error| fastgmad_io_error!(while "writing GMA entry file", error: error, path: path))
+			},
+		)
+	}
+}
+
+/// Returns the offset of every entry we're extracting relative to the start of the GMA entry data, and the total length of the entry data
+fn entry_offsets(file_index: &[GmaEntry]) -> (Vec<(&Path, u64, usize)>, u64) {
+	let mut contents_len = 0;
+	let entries = file_index
+		.iter()
+		.filter_map(|entry| {
+			let offset = contents_len;
+			contents_len += entry.size as u64;
+			entry.path.as_deref().map(|path| (path, offset, entry.size))
+		})
+		.collect();
+	(entries, contents_len)
+}
+
+/// Writes entries whose contents can be read from any thread on a pool of I/O threads
+///
+/// `write_entry` is given a scratch buffer that belongs to the I/O thread, and should give up early once the `AtomicBool` is set.
+fn write_entries_on_pool(
+	conf: &ExtractGmaConfig,
+	#[cfg(feature = "binary")] total_size: u64,
+	entries: &[(&Path, u64, usize)],
+	write_entry: impl Fn(&mut Vec<u8>, &Path, u64, usize, &AtomicBool) -> Result<(), FastGmadError> + Sync,
+) -> Result<(), FastGmadError> {
+	#[cfg(feature = "binary")]
+	let mut progress = if !conf.noprogress {
+		Some(crate::util::ProgressPrinter::new(total_size))
+	} else {
+		None
+	};
+
+	let head = AtomicUsize::new(0);
+	let stopped = AtomicBool::new(false);
+	let (tx, rx) = std::sync::mpsc::channel::<Result<usize, FastGmadError>>();
+
+	std::thread::scope(|scope| {
+		for _ in 0..entries.len().min(conf.max_io_threads.get()) {
+			let tx = tx.clone();
+			let (head, stopped, write_entry) = (&head, &stopped, &write_entry);
+			scope.spawn(move || {
+				let mut buf = Vec::new();
+				while !stopped.load(std::sync::atomic::Ordering::SeqCst) {
+					let (path, offset, size) = match entries.get(head.fetch_add(1, std::sync::atomic::Ordering::SeqCst)) {
+						Some(entry) => *entry,
+						None => break,
+					};
+
+					let res = write_entry(&mut buf, path, offset, size, stopped);
+					if tx.send(res.map(|_| size)).is_err() {
+						break;
+					}
+				}
+			});
+		}
+		drop(tx);
+
+		// Stop the I/O threads if we bail out early
+		struct StopOnDrop<'a>(&'a AtomicBool);
+		impl Drop for StopOnDrop<'_> {
+			fn drop(&mut self) {
+				self.0.store(true, std::sync::atomic::Ordering::SeqCst);
 			}
-			#[cfg(not(any(unix, windows)))]
-			{
-				match String::from_utf8(path) {
-					Ok(path) => Some(PathBuf::from(path)),
+		}
+		let _stop = StopOnDrop(&stopped);
+
+		while let Ok(res) = rx.recv() {
+			#[cfg(feature = "binary")]
+			if crate::ctrlc_handling::cancelled() {
+				return Err(fastgmad_error!(error: Aborted));
+			}
+
+			let _size = res?;
+
+			#[cfg(feature = "binary")]
+			if let Some(progress) = &mut progress {
+				progress.add_progress(_size as u64);
+			}
+		}
+
+		Ok(())
+	})
+}
+
+struct SyncExtractGma;
+impl<R: BufRead + IoSkip> ExtractGma<R> for SyncExtractGma {
+	fn write_entries(
+		conf: &ExtractGmaConfig,
+		mut r: &mut R,
+		#[cfg(feature = "binary")] total_size: u64,
+		file_index: &[GmaEntry],
+	) -> Result<(), FastGmadError> {
+		#[cfg(feature = "binary")]
+		let mut progress = if !conf.noprogress {
+			Some(crate::util::ProgressPrinter::new(total_size))
+		} else {
+			None
+		};
+
+		let mut created = Vec::new();
+		let mut updated = Vec::new();
+		let mut unchanged = 0usize;
+
+		let mut existing_buf = Vec::new();
+		for GmaEntry { path, size } in file_index.iter() {
+			#[cfg(feature = "binary")]
+			if crate::ctrlc_handling::cancelled() {
+				return Err(fastgmad_error!(error: Aborted));
+			}
+
+			let path = match path {
+				Some(path) => path,
+				None => {
+					// Skip past the entry if we couldn't get a path for it
+					r.skip(*size as u64)
+						.map_err(|error| fastgmad_io_error!(while "skipping past GMA entry data", error: error))?;
+					continue;
+				}
+			};
+
+			let existing_size = std::fs::metadata(path)
+				.ok()
+				.filter(|metadata| metadata.is_file())
+				.map(|metadata| metadata.len());
+
+			if existing_size == Some(*size as u64) {
+				// Same size, compare the contents and only rewrite from the first differing chunk onwards
+				let mut w = OpenOptions::new()
+					.read(true)
+					.write(true)
+					.open(path)
+					.map_err(|error| fastgmad_io_error!(while "opening existing file for GMA entry", error: error, path: path))?;
+
+				let mut take = r.take(*size as u64);
+				let mut changed = false;
+				let mut compared = 0;
+				loop {
+					let chunk = take
+						.fill_buf()
+						.map_err(|error| fastgmad_io_error!(while "reading GMA entry data", error: error, path: path))?;
+					if chunk.is_empty() {
+						check_entry_len(compared, *size)
+							.map_err(|error| fastgmad_io_error!(while "reading GMA entry data", error: error, path: path))?;
+						break;
+					}
+
+					let chunk_len = chunk.len();
+					existing_buf.resize(chunk_len, 0);
+					w.read_exact(&mut existing_buf)
+						.map_err(|error| fastgmad_io_error!(while "reading existing file for GMA entry", error: error, path: path))?;
+
+					if existing_buf != chunk {
+						w.seek(SeekFrom::Current(-(chunk_len as i64)))
+							.map_err(|error| fastgmad_io_error!(while "seeking in existing file for GMA entry", error: error, path: path))?;
+						std::io::copy(&mut take, &mut w)
+							.and_then(|copied| check_entry_len(compared + copied, *size))
+							.map_err(|error| fastgmad_io_error!(while "copying GMA entry data", error: error, path: path))?;
+						w.flush()
+							.map_err(|error| fastgmad_io_error!(while "flushing GMA entry file", error: error, path: path))?;
+						changed = true;
+						break;
+					}
+
+					take.consume(chunk_len);
+					compared += chunk_len as u64;
+				}
+				r = take.into_inner();
+
+				if changed {
+					updated.push(path);
+				} else {
+					unchanged += 1;
+				}
+			} else {
+				let w = (|| {
+					if let Some(parent) = path.parent() {
+						if parent != conf.out {
+							std::fs::create_dir_all(parent)
+								.map_err(|error| fastgmad_io_error!(while "creating directory for GMA entry", error: error, path: parent))?;
+						}
+					}
+
+					File::create(path).map_err(|error| fastgmad_io_error!(while "creating file for GMA entry", error: error, path: path))
+				})();
+
+				let mut w = match w {
+					Ok(w) => w,
 					Err(err) => {
-						log::info!(
-							"warning: skipping GMA entry with non-UTF-8 file path: {:?}",
-							String::from_utf8_lossy(err.as_bytes())
-						);
-						None
+						log::warn!("Skipping GMA entry ({err})");
+
+						r.skip(*size as u64)
+							.map_err(|error| fastgmad_io_error!(while "skipping past GMA entry data", error: error))?;
+
+						continue;
 					}
+				};
+
+				let mut take = r.take(*size as u64);
+				let res = std::io::copy(&mut take, &mut w)
+					.and_then(|copied| check_entry_len(copied, *size))
+					.and_then(|_| w.flush())
+					.map_err(|error| fastgmad_io_error!(while "copying GMA entry data", error: error, path: path));
+				remove_if_failed(w, path, res)?;
+				r = take.into_inner();
+
+				if existing_size.is_some() {
+					updated.push(path);
+				} else {
+					created.push(path);
+				}
+			}
+
+			#[cfg(feature = "binary")]
+			if let Some(progress) = &mut progress {
+				progress.add_progress(*size as u64);
+			}
+		}
+
+		#[cfg(feature = "binary")]
+		drop(progress);
+
+		// Delete anything in the output directory that is no longer in the GMA
+		let addon_json_path = conf.out.join("addon.json");
+		let escaped_names_path = conf.out.join(crate::ESCAPED_NAMES_FILE);
+		let keep = file_index
+			.iter()
+			.filter_map(|entry| entry.path.as_deref())
+			.chain([addon_json_path.as_path(), escaped_names_path.as_path()])
+			.collect::<HashSet<_>>();
+
+		let mut deleted = Vec::new();
+		for entry in walkdir::WalkDir::new(&conf.out).min_depth(1).contents_first(true) {
+			let entry = entry.map_err(|error| {
+				let path = error.path().unwrap_or(&conf.out).to_owned();
+				if let Some(io_error) = error.into_io_error() {
+					fastgmad_io_error!(while "walking output directory", error: io_error, path: path)
+				} else {
+					fastgmad_io_error!(while "walking output directory", error: std::io::Error::other("unknown"), path: path)
 				}
+			})?;
+
+			if entry.file_type().is_dir() {
+				// Only succeeds if the directory is now empty
+				std::fs::remove_dir(entry.path()).ok();
+			} else if !keep.contains(entry.path()) {
+				std::fs::remove_file(entry.path())
+					.map_err(|error| fastgmad_io_error!(while "deleting file no longer in GMA", error: error, path: entry.path()))?;
+				deleted.push(entry.into_path());
 			}
+		}
+
+		for path in created.iter() {
+			log::info!("Created {}", path.display());
+		}
+		for path in updated.iter() {
+			log::info!("Updated {}", path.display());
+		}
+		for path in deleted.iter() {
+			log::info!("Deleted {}", path.display());
+		}
+		log::info!(
+			"Sync complete: {} created, {} updated, {} deleted, {unchanged} unchanged",
+			created.len(),
+			updated.len(),
+			deleted.len()
+		);
+
+		Ok(())
+	}
+}
+
+/// Deletes the file we created for a GMA entry if writing it failed, so that a truncated file isn't left behind
+fn remove_if_failed<T>(w: File, path: &Path, res: Result<T, FastGmadError>) -> Result<T, FastGmadError> {
+	if res.is_err() {
+		// Close it first, Windows can't delete open files
+		drop(w);
+		std::fs::remove_file(path).ok();
+	}
+	res
+}
+
+fn check_entry_len(copied: u64, size: usize) -> Result<(), std::io::Error> {
+	if copied == size as u64 {
+		Ok(())
+	} else {
+		Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "GMA entry data is truncated"))
+	}
+}
+
+/// A sibling directory of the output folder that is deleted on drop unless committed
+struct StagingDir(PathBuf);
+impl StagingDir {
+	fn new(out: &Path) -> Result<Self, FastGmadError> {
+		let dir = Self::sibling(out, "staging");
+		std::fs::create_dir_all(&dir).map_err(|error| fastgmad_io_error!(while "creating staging directory", error: error, path: dir))?;
+		Ok(Self(dir))
+	}
+
+	fn sibling(out: &Path, kind: &str) -> PathBuf {
+		let name = out.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+		out.with_file_name(format!(".{name}.fastgmad-{kind}-{}", Uuid::new_v4()))
+	}
+
+	/// Moves the extraction into place
+	///
+	/// If the output folder doesn't exist or is empty, the staging directory is renamed to it in one go.
+	/// Otherwise, the extracted files are moved into it one by one, replacing files at the same paths and leaving anything else in it alone.
+	fn commit(self, out: &Path) -> Result<(), FastGmadError> {
+		let empty = match std::fs::read_dir(out) {
+			Ok(mut entries) => entries.next().is_none(),
+			Err(error) if error.kind() == std::io::ErrorKind::NotFound => true,
+			Err(error) => return Err(fastgmad_io_error!(while "reading output directory", error: error, path: out)),
 		};
 
+		if !empty {
+			// The staging directory deletes whatever is left of it when dropped
+			return Self::merge(&self.0, out);
+		}
+
+		// We can't rename over a directory on every platform
+		if out.exists() {
+			std::fs::remove_dir(out).map_err(|error| fastgmad_io_error!(while "deleting empty output directory", error: error, path: out))?;
+		}
+
+		std::fs::rename(&self.0, out)
+			.map_err(|error| fastgmad_io_error!(while "moving staging directory into place", error: error, paths: (&self.0, out)))?;
+
+		// Nothing left to clean up
+		core::mem::forget(self);
+
+		Ok(())
+	}
+
+	fn merge(from: &Path, to: &Path) -> Result<(), FastGmadError> {
+		let entries = std::fs::read_dir(from).map_err(|error| fastgmad_io_error!(while "reading staging directory", error: error, path: from))?;
+		for entry in entries {
+			let entry = entry.map_err(|error| fastgmad_io_error!(while "reading staging directory", error: error, path: from))?;
+			let from = entry.path();
+			let to = to.join(entry.file_name());
+
+			let is_dir = entry
+				.file_type()
+				.map_err(|error| fastgmad_io_error!(while "reading staging directory", error: error, path: &from))?
+				.is_dir();
+
+			match std::fs::symlink_metadata(&to) {
+				Ok(existing) if existing.is_dir() && is_dir => {
+					Self::merge(&from, &to)?;
+					continue;
+				}
+				Ok(existing) if existing.is_dir() != is_dir => {
+					// Same as a non-atomic extraction, which can't create a file where a directory is or vice versa
+					log::warn!("Skipping GMA entry ({} is in the way)", to.display());
+					continue;
+				}
+				_ => {}
+			}
+
+			std::fs::rename(&from, &to)
+				.map_err(|error| fastgmad_io_error!(while "moving extracted file into place", error: error, paths: (from, to)))?;
+		}
+		Ok(())
+	}
+}
+impl Drop for StagingDir {
+	fn drop(&mut self) {
+		std::fs::remove_dir_all(&self.0).ok();
+	}
+}
+
+/// Everything before the file index of a GMA that we care about
+struct GmaHeader {
+	timestamp: u64,
+	title: Vec<u8>,
+	addon_json: Vec<u8>,
+}
+impl GmaHeader {
+	fn read(r: &mut impl BufRead) -> Result<Self, FastGmadError> {
+		let mut buf = Vec::new();
+
+		{
+			let mut magic = [0u8; 4];
+			let res = r.read_exact(&mut magic);
+			if let Err(error) = res {
+				if error.kind() != std::io::ErrorKind::UnexpectedEof {
+					return Err(fastgmad_io_error!(while "reading GMA magic bytes", error: error));
+				}
+			}
+			if magic != GMA_MAGIC {
+				return Err(fastgmad_io_error!(error: std::io::Error::new(std::io::ErrorKind::InvalidData, "File is not in GMA format")));
+			}
+		}
+
+		let version = r
+			.read_u8()
+			.map_err(|error| fastgmad_io_error!(while "reading version byte", error: error))?;
+		if version != GMA_VERSION {
+			log::warn!("File is in GMA version {version}, expected version {GMA_VERSION}, reading anyway...");
+		}
+
+		// SteamID (unused)
+		r.read_exact(&mut [0u8; 8])
+			.map_err(|error| fastgmad_io_error!(while "reading SteamID", error: error))?;
+
+		// Timestamp
+		let timestamp = r
+			.read_u64::<LE>()
+			.map_err(|error| fastgmad_io_error!(while "reading timestamp", error: error))?;
+
+		if version > 1 {
+			// Required content
+			loop {
+				buf.clear();
+
+				let content = r
+					.read_nul_str(&mut buf)
+					.map_err(|error| fastgmad_io_error!(while "reading required content", error: error))?;
+
+				if content.is_empty() {
+					break;
+				}
+			}
+		}
+
+		// Addon name
+		let title = {
+			buf.clear();
+
+			let title = r
+				.read_nul_str(&mut buf)
+				.map_err(|error| fastgmad_io_error!(while "reading addon name", error: error))?;
+
+			title.to_vec()
+		};
+
+		// addon.json
+		let addon_json = {
+			buf.clear();
+
+			let addon_json = r
+				.read_nul_str(&mut buf)
+				.map_err(|error| fastgmad_io_error!(while "reading addon description", error: error))?;
+
+			addon_json.to_vec()
+		};
+
+		// Addon author
+		r.skip_nul_str()
+			.map_err(|error| fastgmad_io_error!(while "reading addon author", error: error))?;
+
+		// Addon version (unused)
+		r.read_exact(&mut [0u8; 4])
+			.map_err(|error| fastgmad_io_error!(while "reading addon version", error: error))?;
+
+		Ok(Self {
+			timestamp,
+			title,
+			addon_json,
+		})
+	}
+}
+
+#[derive(serde::Serialize)]
+struct StubAddonJson<'a> {
+	title: Cow<'a, str>,
+	description: Cow<'a, str>,
+}
+
+struct GmaEntry {
+	path: Option<PathBuf>,
+	size: usize,
+}
+impl GmaEntry {
+	fn new(base_path: &Path, path: Option<OsString>, size: i64) -> Result<Self, FastGmadError> {
 		let size = match usize::try_from(size) {
 			Ok(size) => size,
 			Err(_) => {
@@ -482,8 +1385,12 @@ impl GmaEntry {
 		};
 
 		let path = path.and_then(|path| {
+			// Joining an absolute path replaces the base path, so absolute paths are as dangerous as .. segments
 			let path = Path::new(&path);
-			if path.components().any(|c| matches!(c, Component::ParentDir | Component::Prefix(_))) {
+			if path
+				.components()
+				.any(|c| matches!(c, Component::ParentDir | Component::RootDir | Component::Prefix(_)))
+			{
 				log::warn!("Skipping GMA entry with invalid file path: {:?}", path);
 				None
 			} else {
@@ -494,3 +1401,31 @@ impl GmaEntry {
 		Ok(Self { path, size })
 	}
 }
+
+#[test]
+fn test_stopped_entry_is_removed() {
+	let dir = std::env::temp_dir().join(format!("fastgmad-stopped-entry-{}", Uuid::new_v4()));
+	std::fs::create_dir_all(&dir).unwrap();
+
+	let src = dir.join("src.bin");
+	std::fs::write(&src, vec![b'-'; 1024]).unwrap();
+	let src = File::open(&src).unwrap();
+
+	let conf = ExtractGmaConfig {
+		out: dir.clone(),
+		..Default::default()
+	};
+	let path = dir.join("entry.bin");
+
+	// The pool has been told to stop before the entry was written
+	let stopped = AtomicBool::new(true);
+	let res = PreadExtractGma::write_entry(&conf, &src, &mut Vec::new(), 16, &path, 0, 1024, &stopped);
+	assert!(res.is_err());
+	assert!(!path.exists(), "partially written entry was left behind");
+
+	let stopped = AtomicBool::new(false);
+	PreadExtractGma::write_entry(&conf, &src, &mut Vec::new(), 16, &path, 0, 1024, &stopped).unwrap();
+	assert_eq!(std::fs::read(&path).unwrap().len(), 1024);
+
+	std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,127 @@
+use std::borrow::Cow;
+
+/// What to do with GMA entries whose file names can't be created on every OS (mostly Windows)
+///
+/// This covers reserved device names (`con.lua`, `aux.vmt`), characters that are illegal on Windows (`:`, `?`, ...)
+/// and names ending in a dot or space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PortableNames {
+	/// Don't check file names
+	#[default]
+	Off,
+
+	/// Fail the extraction before anything is written, including the output folder
+	Fail,
+
+	/// Skip the entry
+	Skip,
+
+	/// Percent-encode the offending characters and record the original name so that the .GMA can be recreated from the extracted files
+	///
+	/// `%` is also encoded in the escaped parts of the name. The extraction fails if an escaped name is the same as another entry's name.
+	Escape,
+}
+impl std::str::FromStr for PortableNames {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"off" => Ok(Self::Off),
+			"fail" => Ok(Self::Fail),
+			"skip" => Ok(Self::Skip),
+			"escape" => Ok(Self::Escape),
+			_ => Err(()),
+		}
+	}
+}
+
+const RESERVED_NAMES: &[&str] = &[
+	"CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5",
+	"LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+fn is_illegal_char(c: char) -> bool {
+	matches!(c, '<' | '>' | ':' | '"' | '|' | '?' | '*' | '\\') || c.is_ascii_control()
+}
+
+fn is_reserved_name(component: &str) -> bool {
+	let stem = component.split('.').next().unwrap_or_default().trim_end_matches(' ');
+	RESERVED_NAMES.iter().any(|reserved| reserved.eq_ignore_ascii_case(stem))
+}
+
+fn is_portable_component(component: &str) -> bool {
+	!component.chars().any(is_illegal_char) && !is_reserved_name(component) && !component.ends_with(['.', ' '])
+}
+
+/// Whether every component of a `/` separated path can be created on every OS
+pub(crate) fn is_portable(path: &str) -> bool {
+	path.split('/')
+		.all(|component| matches!(component, "" | "." | "..") || is_portable_component(component))
+}
+
+/// Percent-encodes the parts of a `/` separated path that can't be created on every OS
+///
+/// `%` is encoded too in the components that are escaped, so that an escaped component can't be mistaken for an escape sequence.
+pub(crate) fn escape(path: &str) -> Cow<'_, str> {
+	if is_portable(path) {
+		return Cow::Borrowed(path);
+	}
+
+	let components = path.split('/').map(|component| {
+		if matches!(component, "" | "." | "..") || is_portable_component(component) {
+			return Cow::Borrowed(component);
+		}
+
+		let chars = component.chars().collect::<Vec<_>>();
+		let mut escaped = chars.iter().map(|c| *c == '%' || is_illegal_char(*c)).collect::<Vec<_>>();
+		if is_reserved_name(component) {
+			escaped[0] = true;
+		}
+		for i in (0..chars.len()).rev() {
+			if !matches!(chars[i], '.' | ' ') {
+				break;
+			}
+			escaped[i] = true;
+		}
+
+		let mut out = String::with_capacity(component.len() * 3);
+		for (c, escaped) in chars.into_iter().zip(escaped) {
+			if escaped {
+				let mut utf8 = [0u8; 4];
+				for byte in c.encode_utf8(&mut utf8).bytes() {
+					out.push_str(&format!("%{byte:02X}"));
+				}
+			} else {
+				out.push(c);
+			}
+		}
+		Cow::Owned(out)
+	});
+
+	Cow::Owned(components.collect::<Vec<_>>().join("/"))
+}
+
+#[test]
+fn test_portable_names() {
+	assert!(is_portable("lua/autorun/test.lua"));
+	assert!(is_portable("materials/console.vmt"));
+	assert!(!is_portable("lua/con.lua"));
+	assert!(!is_portable("materials/AUX.vmt"));
+	assert!(!is_portable("materials/lpt1"));
+	assert!(!is_portable("lua/test.lua."));
+	assert!(!is_portable("lua/test /a.lua"));
+	assert!(!is_portable("lua/a:b.lua"));
+	assert!(!is_portable("lua/a\\b.lua"));
+
+	assert_eq!(escape("lua/autorun/test.lua"), "lua/autorun/test.lua");
+	assert_eq!(escape("lua/con.lua"), "lua/%63on.lua");
+	assert_eq!(escape("lua/test.lua."), "lua/test.lua%2E");
+	assert_eq!(escape("lua/test. /a.lua"), "lua/test%2E%20/a.lua");
+	assert_eq!(escape("lua/a:b?.lua"), "lua/a%3Ab%3F.lua");
+	assert_eq!(escape("lua/%63on.lua"), "lua/%63on.lua");
+	assert_eq!(escape("lua/100%/a%:b.lua"), "lua/100%/a%25%3Ab.lua");
+
+	for path in ["lua/con.lua", "lua/test.lua.", "lua/a:b?.lua", "nul/aux.txt"] {
+		assert!(is_portable(&escape(path)), "{path}");
+	}
+}
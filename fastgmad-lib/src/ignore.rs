@@ -0,0 +1,517 @@
+use crate::{
+	error::{fastgmad_io_error, FastGmadError},
+	whitelist::GlobSet,
+};
+use std::{
+	collections::HashMap,
+	path::{Path, PathBuf},
+	sync::{Arc, RwLock},
+};
+
+/// Ignore files that can be put in any folder of an addon, in `.gitignore` syntax
+pub const GMADIGNORE_FILE: &str = ".gmadignore";
+
+/// Read alongside [`GMADIGNORE_FILE`] if [`crate::create::CreateGmaConfig::respect_gitignore`] is set
+pub const GITIGNORE_FILE: &str = ".gitignore";
+
+/// How the globs in addon.json's `ignore` list are matched
+///
+/// Defaults to [`IgnoreSyntax::Gmad`] so that existing ignore lists keep ignoring the same files; gitignore semantics are opt-in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IgnoreSyntax {
+	/// `.gitignore` semantics: `**`, negation with `!`, directory-only patterns ending in `/`, and patterns anchored to the addon folder
+	/// if they contain a `/`, otherwise matching a file or folder name at any depth
+	Gitignore,
+
+	/// gmad's semantics, which ignore lists are written for: `*` matches any characters including `/`, and every glob must match the
+	/// whole path
+	#[default]
+	Gmad,
+}
+impl std::str::FromStr for IgnoreSyntax {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"gitignore" => Ok(Self::Gitignore),
+			"gmad" => Ok(Self::Gmad),
+			_ => Err(()),
+		}
+	}
+}
+impl std::fmt::Display for IgnoreSyntax {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			Self::Gitignore => "gitignore",
+			Self::Gmad => "gmad",
+		})
+	}
+}
+
+/// A compiled `ignore` list
+#[derive(Debug, Clone)]
+pub struct IgnoreList(IgnoreListInner);
+
+#[derive(Debug, Clone)]
+enum IgnoreListInner {
+	Gitignore {
+		patterns: Vec<IgnorePattern>,
+
+		/// Every pattern compiled into a single automaton
+		set: regex::RegexSet,
+	},
+	Gmad(GlobSet),
+}
+
+impl IgnoreList {
+	/// Compiles a list of ignore patterns
+	///
+	/// With [`IgnoreSyntax::Gitignore`], empty patterns and patterns starting with `#` are skipped, like blank lines and comments in a `.gitignore`.
+	pub fn new(patterns: &[String], syntax: IgnoreSyntax) -> Self {
+		Self(match syntax {
+			IgnoreSyntax::Gitignore => {
+				let patterns = patterns.iter().filter_map(|pattern| IgnorePattern::parse(pattern)).collect::<Vec<_>>();
+
+				// Every pattern translates to a valid regex, so this can only fail if the set is too big to compile
+				let set = regex::RegexSetBuilder::new(patterns.iter().map(IgnorePattern::to_regex))
+					.size_limit(usize::MAX)
+					.dfa_size_limit(64 * 1024 * 1024)
+					.build()
+					.expect("failed to compile ignore patterns");
+
+				IgnoreListInner::Gitignore { patterns, set }
+			}
+			IgnoreSyntax::Gmad => IgnoreListInner::Gmad(GlobSet::new(patterns.iter().map(String::as_str))),
+		})
+	}
+
+	/// Whether the list has no patterns
+	pub fn is_empty(&self) -> bool {
+		match &self.0 {
+			IgnoreListInner::Gitignore { patterns, .. } => patterns.is_empty(),
+			IgnoreListInner::Gmad(globs) => globs.is_empty(),
+		}
+	}
+
+	/// Check if a file is ignored, given its path relative to the addon folder with `/` separators
+	pub fn is_ignored(&self, path: &str) -> bool {
+		match &self.0 {
+			IgnoreListInner::Gitignore { patterns, .. } if patterns.is_empty() => false,
+			IgnoreListInner::Gitignore { .. } => {
+				// Like git, check each parent folder first, because files inside an ignored folder can't be re-included by a negated pattern
+				path.match_indices('/')
+					.map(|(slash, _)| (&path[..slash], true))
+					.chain(std::iter::once((path, false)))
+					.any(|(path, is_dir)| self.matched(path, is_dir) == Some(true))
+			}
+			IgnoreListInner::Gmad(globs) => globs.is_match(path),
+		}
+	}
+
+	/// Whether the last pattern matching `path` ignores it (`Some(true)`) or re-includes it (`Some(false)`)
+	fn matched(&self, path: &str, is_dir: bool) -> Option<bool> {
+		match &self.0 {
+			IgnoreListInner::Gitignore { patterns, set } => set
+				.matches(path)
+				.iter()
+				.rev()
+				.map(|index| &patterns[index])
+				.find(|pattern| is_dir || !pattern.dir_only)
+				.map(|pattern| !pattern.negated),
+
+			// gmad only ever matches files
+			IgnoreListInner::Gmad(globs) => (!is_dir && globs.is_match(path)).then_some(true),
+		}
+	}
+}
+
+/// The rules in addon.json's `ignore` list, and in `.gmadignore` (and optionally `.gitignore`) files in any folder of the addon
+///
+/// Ignore files are read the first time a file in their folder is checked, and use `.gitignore` syntax regardless of [`IgnoreSyntax`].
+/// Rules in deeper folders take precedence over rules in their parents, `.gmadignore` takes precedence over `.gitignore` in the same folder,
+/// and all of them take precedence over addon.json.
+pub(crate) struct IgnoreFiles<'a> {
+	folder: &'a Path,
+	addon_json: IgnoreList,
+	respect_gitignore: bool,
+	loaded: RwLock<HashMap<PathBuf, Arc<[IgnoreList]>>>,
+}
+impl<'a> IgnoreFiles<'a> {
+	pub(crate) fn new(folder: &'a Path, addon_json: IgnoreList, respect_gitignore: bool) -> Self {
+		Self {
+			folder,
+			addon_json,
+			respect_gitignore,
+			loaded: RwLock::new(HashMap::new()),
+		}
+	}
+
+	/// Whether a file is one of the ignore files this reads, which never belong in a .GMA
+	pub(crate) fn is_ignore_file(relative_path: &str) -> bool {
+		matches!(relative_path.rsplit('/').next(), Some(GMADIGNORE_FILE | GITIGNORE_FILE))
+	}
+
+	/// Check if a file is ignored
+	///
+	/// `disk_path` is the file's path relative to the addon folder as it is on disk, which ignore files are matched against.
+	/// `gma_path` is the path it will have in the .GMA, which addon.json's `ignore` list is matched against.
+	pub(crate) fn is_ignored(&self, disk_path: &str, gma_path: &str) -> Result<bool, FastGmadError> {
+		// Where each component of the path starts and ends
+		let mut components = Vec::new();
+		let mut start = 0;
+		for (slash, _) in disk_path.match_indices('/').chain(std::iter::once((disk_path.len(), ""))) {
+			components.push((start, slash));
+			start = slash + 1;
+		}
+
+		let mut lists = Vec::with_capacity(components.len());
+		for &(start, _) in &components {
+			// The folder the component is in
+			lists.push(self.load(&disk_path[..start.saturating_sub(1)])?);
+		}
+
+		let mut matched = None;
+		for (depth, &(_, end)) in components.iter().enumerate() {
+			let is_dir = depth + 1 < components.len();
+
+			matched = lists[..=depth]
+				.iter()
+				.zip(&components)
+				.rev()
+				.flat_map(|(lists, &(start, _))| lists.iter().rev().map(move |list| (start, list)))
+				.find_map(|(start, list)| list.matched(&disk_path[start..end], is_dir));
+
+			if matched == Some(true) {
+				return Ok(true);
+			}
+		}
+
+		Ok(match matched {
+			Some(ignored) => ignored,
+			None => self.addon_json.is_ignored(gma_path),
+		})
+	}
+
+	/// Reads the ignore files in a folder, in increasing order of precedence
+	fn load(&self, dir: &str) -> Result<Arc<[IgnoreList]>, FastGmadError> {
+		let dir = self.folder.join(dir);
+		if let Some(lists) = self.loaded.read().unwrap().get(&dir) {
+			return Ok(lists.clone());
+		}
+
+		let mut lists = Vec::new();
+		for (file_name, enabled) in [(GITIGNORE_FILE, self.respect_gitignore), (GMADIGNORE_FILE, true)] {
+			if !enabled {
+				continue;
+			}
+
+			let path = dir.join(file_name);
+			let patterns = match std::fs::read_to_string(&path) {
+				Ok(patterns) => patterns,
+				Err(error) if error.kind() == std::io::ErrorKind::NotFound => continue,
+				Err(error) => return Err(fastgmad_io_error!(while "reading ignore file", error: error, path: path)),
+			};
+
+			lists.push(IgnoreList::new(
+				&patterns.lines().map(ToOwned::to_owned).collect::<Vec<_>>(),
+				IgnoreSyntax::Gitignore,
+			));
+		}
+
+		let lists = Arc::<[IgnoreList]>::from(lists);
+		self.loaded.write().unwrap().insert(dir, lists.clone());
+		Ok(lists)
+	}
+}
+
+#[derive(Debug, Clone)]
+struct IgnorePattern {
+	negated: bool,
+	dir_only: bool,
+
+	/// Patterns containing a `/` are matched against the whole path, others against the file or folder name
+	anchored: bool,
+
+	segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+	/// `**` as a whole path segment, which matches any number of folders
+	DoubleStar,
+	Glob(Vec<Token>),
+}
+
+#[derive(Debug, Clone)]
+enum Token {
+	Literal(char),
+	Any,
+	Star,
+	Class { negated: bool, ranges: Vec<(char, char)> },
+}
+impl IgnorePattern {
+	fn parse(pattern: &str) -> Option<Self> {
+		if pattern.starts_with('#') {
+			return None;
+		}
+
+		// Trailing spaces are ignored unless they are escaped
+		let mut pattern = pattern.trim_end_matches(['\n', '\r']);
+		while pattern.ends_with(' ') && !pattern.ends_with("\\ ") {
+			pattern = &pattern[..pattern.len() - 1];
+		}
+
+		let negated = match pattern.strip_prefix('!') {
+			Some(stripped) => {
+				pattern = stripped;
+				true
+			}
+			None => false,
+		};
+
+		let dir_only = match pattern.strip_suffix('/') {
+			Some(stripped) => {
+				pattern = stripped;
+				true
+			}
+			None => false,
+		};
+
+		if pattern.is_empty() {
+			return None;
+		}
+
+		let anchored = pattern.contains('/');
+		let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+
+		let segments = pattern
+			.split('/')
+			.map(|segment| {
+				if segment == "**" && anchored {
+					Segment::DoubleStar
+				} else {
+					Segment::Glob(Self::parse_glob(segment))
+				}
+			})
+			.collect();
+
+		Some(Self {
+			negated,
+			dir_only,
+			anchored,
+			segments,
+		})
+	}
+
+	fn parse_glob(glob: &str) -> Vec<Token> {
+		let mut tokens = Vec::new();
+		let mut chars = glob.chars().peekable();
+		while let Some(c) = chars.next() {
+			tokens.push(match c {
+				'\\' => Token::Literal(chars.next().unwrap_or('\\')),
+				'?' => Token::Any,
+				'*' => {
+					// Consecutive stars within a segment are the same as one
+					while chars.next_if_eq(&'*').is_some() {}
+					Token::Star
+				}
+				'[' => match Self::parse_class(&mut chars.clone()) {
+					Some((token, consumed)) => {
+						chars.nth(consumed - 1);
+						token
+					}
+					None => Token::Literal('['),
+				},
+				c => Token::Literal(c),
+			});
+		}
+		tokens
+	}
+
+	/// Parses a character class after its opening `[`, returning the number of characters consumed, or `None` if it isn't closed
+	fn parse_class(chars: &mut impl Iterator<Item = char>) -> Option<(Token, usize)> {
+		let mut consumed = 0;
+		let mut next = || {
+			consumed += 1;
+			chars.next()
+		};
+
+		let mut ranges = Vec::new();
+		let mut c = next()?;
+
+		let negated = c == '!' || c == '^';
+		if negated {
+			c = next()?;
+		}
+
+		// A `]` straight after the opening bracket is a literal
+		let mut first = true;
+		loop {
+			if c == ']' && !first {
+				break;
+			}
+			first = false;
+
+			let start = if c == '\\' { next()? } else { c };
+			c = next()?;
+
+			if c == '-' {
+				let end = next()?;
+				if end == ']' {
+					// A trailing `-` is a literal
+					ranges.push((start, start));
+					ranges.push(('-', '-'));
+					break;
+				}
+				let end = if end == '\\' { next()? } else { end };
+
+				// Like git, a reversed range matches nothing
+				if start <= end {
+					ranges.push((start, end));
+				}
+				c = next()?;
+			} else {
+				ranges.push((start, start));
+			}
+		}
+
+		Some((Token::Class { negated, ranges }, consumed))
+	}
+
+	/// Translates the pattern to a regex that matches the paths it applies to, relative to the folder it's in
+	fn to_regex(&self) -> String {
+		let mut regex = String::from("(?s)^");
+
+		// Patterns without a `/` match a file or folder name at any depth
+		if !self.anchored {
+			regex.push_str("(?:.*/)?");
+		}
+
+		let mut separate = false;
+		for (i, segment) in self.segments.iter().enumerate() {
+			match segment {
+				// A trailing `/**` matches everything inside the folder, but not the folder itself
+				Segment::DoubleStar if i == self.segments.len() - 1 => {
+					if separate {
+						regex.push('/');
+					}
+					regex.push_str(".+");
+				}
+
+				Segment::DoubleStar => {
+					if separate {
+						regex.push('/');
+					}
+					regex.push_str("(?:.*/)?");
+					separate = false;
+				}
+
+				Segment::Glob(tokens) => {
+					if separate {
+						regex.push('/');
+					}
+					for token in tokens {
+						match token {
+							Token::Literal(c) => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+							Token::Any => regex.push_str("[^/]"),
+							Token::Star => regex.push_str("[^/]*"),
+							Token::Class { negated, ranges } => {
+								regex.push_str(if *negated { "[^/" } else { "[" });
+								for (start, end) in ranges {
+									regex.push_str(&format!("\\x{{{:x}}}-\\x{{{:x}}}", *start as u32, *end as u32));
+								}
+								if ranges.is_empty() && !negated {
+									// Matches nothing
+									regex.push_str("^\\x{0}-\\x{10ffff}");
+								}
+								regex.push(']');
+							}
+						}
+					}
+					separate = true;
+				}
+			}
+		}
+
+		regex.push('$');
+		regex
+	}
+}
+
+#[test]
+fn test_gitignore() {
+	let ignored = |path: &str, patterns: &[&str]| {
+		IgnoreList::new(
+			&patterns.iter().map(|pattern| pattern.to_string()).collect::<Vec<_>>(),
+			IgnoreSyntax::Gitignore,
+		)
+		.is_ignored(path)
+	};
+
+	// Unanchored patterns match names at any depth
+	assert!(ignored("lol.txt", &["lol.txt"]));
+	assert!(ignored("lua/lol.txt", &["lol.txt"]));
+	assert!(ignored("lua/autorun/test.psd", &["*.psd"]));
+	assert!(ignored(".gitattributes", &[".git*"]));
+	assert!(ignored("src/.git/HEAD", &[".git"]));
+	assert!(!ignored("lol.txt", &[]));
+
+	// `*` doesn't match across folders, unlike gmad
+	assert!(ignored("lua/hello.lua", &["lua/*.lua"]));
+	assert!(!ignored("lua/autorun/hello.lua", &["lua/*.lua"]));
+	assert!(ignored("lua/autorun/hello.lua", &["lua/*"]));
+
+	// Anchoring
+	assert!(ignored("build.lua", &["/build.lua"]));
+	assert!(!ignored("lua/build.lua", &["/build.lua"]));
+	assert!(!ignored("src/lua/hello.lua", &["lua/hello.lua"]));
+
+	// `**`
+	assert!(ignored("a/b/c/test.psd", &["**/*.psd"]));
+	assert!(ignored("test.psd", &["**/*.psd"]));
+	assert!(ignored("lua/a/b/test.lua", &["lua/**/test.lua"]));
+	assert!(ignored("lua/test.lua", &["lua/**/test.lua"]));
+	assert!(ignored("lua/a/b/test.lua", &["lua/**"]));
+	assert!(!ignored("lua.txt", &["lua/**"]));
+	assert!(ignored("lua/a\nb/test.lua", &["lua/**"]));
+	assert!(ignored("a\nb/test.psd", &["**/*.psd"]));
+
+	// Folder-only patterns
+	assert!(ignored("build/out.lua", &["build/"]));
+	assert!(ignored("lua/build/out.lua", &["build/"]));
+	assert!(!ignored("lua/build", &["build/"]));
+
+	// Negation
+	assert!(!ignored("lua/keep.lua", &["lua/*.lua", "!lua/keep.lua"]));
+	assert!(ignored("lua/other.lua", &["lua/*.lua", "!lua/keep.lua"]));
+	assert!(ignored("lua/keep.lua", &["!lua/keep.lua", "lua/*.lua"]));
+	assert!(!ignored("src/keep.lua", &["src/**", "!src/keep.lua"]));
+	assert!(!ignored("docs/README.md", &["*.md", "!README.md"]));
+
+	// Files inside an ignored folder can't be re-included
+	assert!(ignored("src/keep.lua", &["src/", "!src/keep.lua"]));
+
+	// Character classes, escapes, comments and trailing spaces
+	assert!(ignored("test1.lua", &["test[0-9].lua"]));
+	assert!(!ignored("testa.lua", &["test[0-9].lua"]));
+	assert!(ignored("testa.lua", &["test[!0-9].lua"]));
+	assert!(ignored("test].lua", &["test[]].lua"]));
+	assert!(ignored("[test].lua", &["\\[test\\].lua"]));
+	assert!(ignored("!important.txt", &["\\!important.txt"]));
+	assert!(!ignored("#comment", &["#comment"]));
+	assert!(ignored("#comment", &["\\#comment"]));
+	assert!(ignored("lol.txt", &["lol.txt   "]));
+	assert!(ignored("a?c", &["a\\?c"]));
+	assert!(!ignored("abc", &["a\\?c"]));
+	assert!(!ignored("b.lua", &["[z-a].lua"]));
+	assert!(ignored("b.lua", &["[!z-a].lua"]));
+	assert!(!ignored("a/b", &["a[!x]b"]));
+}
+
+#[test]
+fn test_gmad_compatibility() {
+	let list = IgnoreList::new(&["lua/*.lua".to_string()], IgnoreSyntax::Gmad);
+	assert!(list.is_ignored("lua/hello.lua"));
+	assert!(list.is_ignored("lua/autorun/hello.lua"));
+	assert!(!list.is_ignored("materials/hello.lua"));
+}
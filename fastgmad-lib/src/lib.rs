@@ -5,9 +5,23 @@
 //! `workshop` - Workshop publishing support
 //!
 //! `binary` - Recommended if you're using fastgmad in a binary as this enables some binary-related helpers.
+//! This includes Ctrl-C handling: with `binary`, creation and extraction abort and clean up after themselves when Ctrl-C is pressed.
+//! Library builds without it have no way to cancel an operation that is in progress.
+//!
+//! `mmap` - Memory-mapped GMA reading
 
 #![cfg_attr(not(feature = "binary"), warn(missing_docs))]
 #![allow(clippy::unnecessary_literal_unwrap)]
+// The original whitelist tests predate these lints
+#![cfg_attr(
+	test,
+	allow(
+		clippy::explicit_auto_deref,
+		clippy::borrow_deref_ref,
+		clippy::needless_borrow,
+		clippy::unnecessary_to_owned
+	)
+)]
 
 #[cfg(all(feature = "binary", feature = "workshop"))]
 compile_error!("Cannot enable both `binary` and `workshop` features (`binary` implies `workshop`)");
@@ -15,8 +29,14 @@ compile_error!("Cannot enable both `binary` and `workshop` features (`binary` im
 const GMA_MAGIC: &[u8] = b"GMAD";
 const GMA_VERSION: u8 = 3;
 
+/// Maps escaped file names back to their original names, see [`extract::PortableNames::Escape`]
+const ESCAPED_NAMES_FILE: &str = ".fastgmad-escaped-names.json";
+
 mod util;
 
+#[cfg(feature = "binary")]
+mod ctrlc_handling;
+
 /// FastGMAD errors
 pub mod error;
 
@@ -29,6 +49,13 @@ pub mod create;
 /// GMA extraction
 pub mod extract;
 
+#[cfg(feature = "mmap")]
+/// Memory-mapped GMA reading
+pub mod mmap;
+
+/// Matching of the `ignore` list in addon.json
+pub mod ignore;
+
 /// GMA file pattern whitelist
 pub mod whitelist;
 
@@ -38,7 +65,7 @@ pub mod workshop;
 
 #[cfg(feature = "binary")]
 pub mod bin_prelude {
-	pub use crate::util::PrintHelp;
+	pub use crate::{ctrlc_handling::exit_ctrlc, util::PrintHelp};
 	pub use libloading;
 	pub use log;
 }
@@ -0,0 +1,219 @@
+use crate::{
+	error::{fastgmad_error, fastgmad_io_error, FastGmadError},
+	GMA_MAGIC,
+};
+use byteorder::{ReadBytesExt, LE};
+use memmap2::Mmap;
+use std::{
+	fs::File,
+	io::Read,
+	ops::Range,
+	path::{Path, PathBuf},
+	time::SystemTime,
+};
+
+/// A memory-mapped .GMA file
+///
+/// The file is locked with a shared lock for as long as it is mapped, so that anything that respects file locks can't write to it in the meantime.
+/// This isn't enforced on every OS, so [`MmapGma::verify`] checks that the file's size and modification time haven't changed since it was mapped.
+/// Extracting with [`crate::extract::extract_mmap_gma`] does this automatically.
+///
+/// Note that if another process truncates the file while it's mapped, touching the missing part of the mapping crashes the process (`SIGBUS` on Unix).
+pub struct MmapGma {
+	path: PathBuf,
+	file: File,
+	mmap: Mmap,
+	modified: Option<SystemTime>,
+	timestamp: u64,
+	title: Range<usize>,
+	addon_json: Range<usize>,
+	entries: Vec<MmapGmaEntryIndex>,
+}
+
+struct MmapGmaEntryIndex {
+	path: Range<usize>,
+	contents: Range<usize>,
+	crc: u32,
+}
+
+/// An entry in a [`MmapGma`]
+#[derive(Debug, Clone, Copy)]
+pub struct MmapGmaEntry<'a> {
+	/// The path of the entry, in whatever encoding the .GMA was created with
+	pub path: &'a [u8],
+
+	/// The contents of the entry
+	pub contents: &'a [u8],
+
+	/// The CRC32 of the entry stored in the .GMA (0 if the .GMA was created by fastgmad)
+	pub crc: u32,
+}
+
+impl MmapGma {
+	/// Opens, locks and maps a .GMA file, and reads its file index
+	pub fn open(path: impl AsRef<Path>) -> Result<Self, FastGmadError> {
+		let path = path.as_ref();
+
+		let file = File::open(path).map_err(|error| fastgmad_io_error!(while "opening GMA file", error: error, path: path))?;
+		file.try_lock_shared().map_err(|error| match error {
+			std::fs::TryLockError::WouldBlock => fastgmad_io_error!(
+				while "locking GMA file",
+				error: std::io::Error::new(std::io::ErrorKind::WouldBlock, "GMA file is locked by another process"),
+				path: path
+			),
+			std::fs::TryLockError::Error(error) => fastgmad_io_error!(while "locking GMA file", error: error, path: path),
+		})?;
+
+		let modified = file
+			.metadata()
+			.map_err(|error| fastgmad_io_error!(while "reading GMA file metadata", error: error, path: path))?
+			.modified()
+			.ok();
+
+		// SAFETY: The file is locked, and we check it hasn't been modified before trusting anything we've read out of it
+		let mmap = unsafe { Mmap::map(&file) }.map_err(|error| fastgmad_io_error!(while "memory-mapping GMA file", error: error, path: path))?;
+
+		let mut gma = Self {
+			path: path.to_path_buf(),
+			file,
+			mmap,
+			modified,
+			timestamp: 0,
+			title: 0..0,
+			addon_json: 0..0,
+			entries: Vec::new(),
+		};
+		gma.read_index()
+			.map_err(|error| fastgmad_io_error!(while "reading GMA file index", error: error, path: path))?;
+		gma.verify()?;
+
+		Ok(gma)
+	}
+
+	fn read_index(&mut self) -> Result<(), std::io::Error> {
+		let bytes = &self.mmap[..];
+		let mut r = bytes;
+		let pos = |r: &[u8]| bytes.len() - r.len();
+		let read_nul_str = |r: &mut &[u8]| {
+			let start = pos(r);
+			let len = memchr::memchr(0, r).ok_or_else(|| std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "unterminated string"))?;
+			*r = &r[len + 1..];
+			Ok::<_, std::io::Error>(start..start + len)
+		};
+
+		let mut magic = [0u8; 4];
+		r.read_exact(&mut magic)?;
+		if magic != GMA_MAGIC {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "File is not in GMA format"));
+		}
+
+		let version = r.read_u8()?;
+
+		// SteamID (unused)
+		r.read_u64::<LE>()?;
+
+		self.timestamp = r.read_u64::<LE>()?;
+
+		if version > 1 {
+			// Required content
+			while !read_nul_str(&mut r)?.is_empty() {}
+		}
+
+		self.title = read_nul_str(&mut r)?;
+		self.addon_json = read_nul_str(&mut r)?;
+
+		// Addon author
+		read_nul_str(&mut r)?;
+
+		// Addon version (unused)
+		r.read_u32::<LE>()?;
+
+		let mut entries = Vec::new();
+		while r.read_u32::<LE>()? != 0 {
+			let path = read_nul_str(&mut r)?;
+
+			let size = r.read_i64::<LE>()?;
+			let size = usize::try_from(size).map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid GMA entry size"))?;
+
+			let crc = r.read_u32::<LE>()?;
+
+			entries.push((path, size, crc));
+		}
+
+		let mut offset = pos(r);
+		for (path, size, crc) in entries {
+			let contents = offset
+				..offset
+					.checked_add(size)
+					.filter(|end| *end <= bytes.len())
+					.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "GMA entry data is truncated"))?;
+			offset = contents.end;
+			self.entries.push(MmapGmaEntryIndex { path, contents, crc });
+		}
+
+		Ok(())
+	}
+
+	/// Checks that the file hasn't been resized or modified since it was mapped
+	pub fn verify(&self) -> Result<(), FastGmadError> {
+		let metadata = self
+			.file
+			.metadata()
+			.map_err(|error| fastgmad_io_error!(while "reading GMA file metadata", error: error, path: self.path))?;
+
+		if metadata.len() != self.mmap.len() as u64 || metadata.modified().ok() != self.modified {
+			return Err(fastgmad_error!(error: MappedFileModified(self.path.clone())));
+		}
+
+		Ok(())
+	}
+
+	/// The path of the .GMA file
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	/// The whole .GMA file
+	pub fn as_bytes(&self) -> &[u8] {
+		&self.mmap
+	}
+
+	/// The timestamp stored in the .GMA
+	pub fn timestamp(&self) -> u64 {
+		self.timestamp
+	}
+
+	/// The addon's title
+	pub fn title(&self) -> &[u8] {
+		&self.mmap[self.title.clone()]
+	}
+
+	/// The addon's description, which is usually the addon.json the .GMA was created from
+	pub fn addon_json(&self) -> &[u8] {
+		&self.mmap[self.addon_json.clone()]
+	}
+
+	/// The number of entries in the .GMA
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+
+	/// Whether the .GMA has no entries
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+
+	/// The entries in the .GMA, in the order they are stored
+	pub fn entries(&self) -> impl ExactSizeIterator<Item = MmapGmaEntry<'_>> + '_ {
+		self.entries.iter().map(|entry| MmapGmaEntry {
+			path: &self.mmap[entry.path.clone()],
+			contents: &self.mmap[entry.contents.clone()],
+			crc: entry.crc,
+		})
+	}
+
+	/// Finds an entry by its path
+	pub fn entry(&self, path: &[u8]) -> Option<MmapGmaEntry<'_>> {
+		self.entries().find(|entry| entry.path == path)
+	}
+}
@@ -65,6 +65,7 @@ lazy_static! {
 }
 
 #[test]
+#[allow(clippy::field_reassign_with_default, clippy::useless_conversion)]
 fn test_extract_wiremod_parallel() {
 	let wiremod_test_data = &*WIREMOD_TEST_DATA;
 
@@ -77,6 +78,7 @@ fn test_extract_wiremod_parallel() {
 }
 
 #[test]
+#[allow(clippy::field_reassign_with_default, clippy::useless_conversion)]
 fn test_extract_wiremod_standard() {
 	let wiremod_test_data = &*WIREMOD_TEST_DATA;
 
@@ -89,6 +91,7 @@ fn test_extract_wiremod_standard() {
 }
 
 #[test]
+#[allow(clippy::field_reassign_with_default, clippy::useless_conversion)]
 fn test_create_wiremod_parallel() {
 	let wiremod_test_data = &*WIREMOD_TEST_DATA;
 
@@ -117,6 +120,7 @@ fn test_create_wiremod_parallel() {
 }
 
 #[test]
+#[allow(clippy::field_reassign_with_default, clippy::useless_conversion)]
 fn test_create_wiremod_standard() {
 	let wiremod_test_data = &*WIREMOD_TEST_DATA;
 
@@ -197,3 +201,938 @@ fn verify_extracted_wiremod(dir: &Path, wiremod_test_data: &WiremodTestData) {
 		}
 	}
 }
+
+fn create_test_addon(files: &[(&str, &[u8])]) -> PathBuf {
+	let addon_dir = GMA_TEMP_DIR.join(Uuid::new_v4().to_string());
+	std::fs::create_dir_all(&addon_dir).unwrap();
+	std::fs::write(
+		addon_dir.join("addon.json"),
+		br#"{"title": "Test Addon", "type": "tool", "tags": ["fun"]}"#,
+	)
+	.unwrap();
+	for (path, contents) in files {
+		let path = addon_dir.join(path);
+		std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+		std::fs::write(path, contents).unwrap();
+	}
+	addon_dir
+}
+
+/// Creates a test addon containing `files`, and a config for packing it
+fn test_addon(files: &[(&str, &[u8])]) -> (PathBuf, CreateGmaConfig) {
+	let addon_dir = create_test_addon(files);
+	let config = CreateGmaConfig {
+		folder: addon_dir.clone(),
+		..Default::default()
+	};
+	(addon_dir, config)
+}
+
+/// An extraction config that extracts into a new temporary folder
+fn test_extract_config() -> ExtractGmaConfig {
+	ExtractGmaConfig {
+		out: GMA_TEMP_DIR.join(Uuid::new_v4().to_string()),
+		..Default::default()
+	}
+}
+
+fn create_test_gma(addon_dir: &Path) -> PathBuf {
+	let config = CreateGmaConfig {
+		folder: addon_dir.to_owned(),
+		max_io_threads: NonZeroUsize::new(1).unwrap(),
+		..Default::default()
+	};
+
+	let gma_path = addon_dir.with_extension("gma");
+	fastgmad::create::create_gma(&config, &mut BufWriter::new(File::create(&gma_path).unwrap())).unwrap();
+	gma_path
+}
+
+#[test]
+fn test_extract_sync() {
+	let addon_dir = create_test_addon(&[
+		("lua/autorun/a.lua", b"print('a')"),
+		("lua/autorun/b.lua", b"print('b')"),
+		("materials/c.png", b"not really a png"),
+	]);
+	let gma = create_test_gma(&addon_dir);
+
+	let mut config = test_extract_config();
+	config.sync = true;
+
+	// Initial sync into an empty directory
+	fastgmad::extract::extract_gma(&config, &mut BufReader::new(File::open(&gma).unwrap())).unwrap();
+	assert_eq!(std::fs::read(config.out.join("lua/autorun/a.lua")).unwrap(), b"print('a')");
+
+	// Tamper with the output directory
+	std::fs::write(config.out.join("lua/autorun/a.lua"), b"print('x')").unwrap(); // same size, different contents
+	std::fs::write(config.out.join("lua/autorun/b.lua"), b"print('bb')").unwrap(); // different size
+	std::fs::create_dir_all(config.out.join("lua/stale")).unwrap();
+	std::fs::write(config.out.join("lua/stale/stale.lua"), b"stale").unwrap();
+
+	fastgmad::extract::extract_gma(&config, &mut BufReader::new(File::open(&gma).unwrap())).unwrap();
+	assert_eq!(std::fs::read(config.out.join("lua/autorun/a.lua")).unwrap(), b"print('a')");
+	assert_eq!(std::fs::read(config.out.join("lua/autorun/b.lua")).unwrap(), b"print('b')");
+	assert_eq!(std::fs::read(config.out.join("materials/c.png")).unwrap(), b"not really a png");
+	assert!(!config.out.join("lua/stale").exists());
+	assert!(config.out.join("addon.json").is_file());
+}
+
+#[test]
+fn test_extract_atomic() {
+	let addon_dir = create_test_addon(&[("lua/autorun/a.lua", b"print('a')"), ("materials/c.png", b"not really a png")]);
+	let gma = create_test_gma(&addon_dir);
+
+	let mut config = test_extract_config();
+	config.atomic = true;
+
+	// Nothing there yet, so the staging directory is renamed into place
+	fastgmad::extract::extract_gma(&config, &mut BufReader::new(File::open(&gma).unwrap())).unwrap();
+	assert_eq!(std::fs::read(config.out.join("lua/autorun/a.lua")).unwrap(), b"print('a')");
+
+	// Files that aren't in the GMA must survive, e.g. other addons in garrysmod/addons
+	std::fs::write(config.out.join("previous.txt"), b"previous").unwrap();
+	std::fs::write(config.out.join("lua/autorun/unrelated.lua"), b"unrelated").unwrap();
+	std::fs::write(config.out.join("lua/autorun/a.lua"), b"stale").unwrap();
+
+	fastgmad::extract::extract_gma(&config, &mut BufReader::new(File::open(&gma).unwrap())).unwrap();
+	assert_eq!(std::fs::read(config.out.join("lua/autorun/a.lua")).unwrap(), b"print('a')");
+	assert_eq!(std::fs::read(config.out.join("materials/c.png")).unwrap(), b"not really a png");
+	assert_eq!(std::fs::read(config.out.join("previous.txt")).unwrap(), b"previous");
+	assert_eq!(std::fs::read(config.out.join("lua/autorun/unrelated.lua")).unwrap(), b"unrelated");
+
+	// A truncated GMA must leave the previous output untouched, and no staging directory behind
+	let truncated = gma.with_extension("truncated.gma");
+	let data = std::fs::read(&gma).unwrap();
+	std::fs::write(&truncated, &data[..data.len() - 4]).unwrap();
+	fastgmad::extract::extract_gma(&config, &mut BufReader::new(File::open(&truncated).unwrap())).unwrap_err();
+	assert_eq!(std::fs::read(config.out.join("lua/autorun/a.lua")).unwrap(), b"print('a')");
+	assert_eq!(std::fs::read(config.out.join("previous.txt")).unwrap(), b"previous");
+
+	let out_name = config.out.file_name().unwrap().to_str().unwrap();
+	for entry in std::fs::read_dir(config.out.parent().unwrap()).unwrap() {
+		let name = entry.unwrap().file_name();
+		assert!(!name.to_str().unwrap().starts_with(&format!(".{out_name}.")), "{name:?} left behind");
+	}
+}
+
+#[test]
+fn test_extract_truncated_entries() {
+	let addon_dir = create_test_addon(&[("lua/autorun/a.lua", b"print('a')"), ("materials/c.png", b"not really a png")]);
+	let gma = create_test_gma(&addon_dir);
+
+	// Cut off the CRC and the end of the last entry's data
+	let truncated = gma.with_extension("truncated.gma");
+	let data = std::fs::read(&gma).unwrap();
+	std::fs::write(&truncated, &data[..data.len() - 8]).unwrap();
+
+	for threads in [1, 4] {
+		let mut config = test_extract_config();
+		config.max_io_threads = NonZeroUsize::new(threads).unwrap();
+		let err = fastgmad::extract::extract_gma(&config, &mut BufReader::new(File::open(&truncated).unwrap())).unwrap_err();
+		assert!(err.to_string().contains("truncated"), "{err}");
+
+		let config = test_extract_config();
+		let err = fastgmad::extract::extract_gma_from_file(&config, &mut BufReader::new(File::open(&truncated).unwrap())).unwrap_err();
+		assert!(err.to_string().contains("truncated"), "{err}");
+	}
+
+	let mut config = test_extract_config();
+	config.sync = true;
+	let err = fastgmad::extract::extract_gma(&config, &mut BufReader::new(File::open(&truncated).unwrap())).unwrap_err();
+	assert!(err.to_string().contains("truncated"), "{err}");
+}
+#[test]
+fn test_create_excludes_output_file() {
+	let (addon_dir, mut config) = test_addon(&[("lua/autorun/a.lua", b"print('a')"), ("addon.gma", b"previous build")]);
+
+	fastgmad::create::create_gma(&config, &mut Vec::new()).unwrap_err();
+
+	config.exclude.push(addon_dir.join("addon.gma"));
+	fastgmad::create::create_gma(&config, &mut Vec::new()).unwrap();
+}
+
+#[test]
+fn test_timestamps_and_permissions() {
+	use std::time::{Duration, SystemTime};
+
+	let (addon_dir, mut config) = test_addon(&[("lua/autorun/a.lua", b"print('a')"), ("lua/autorun/b.lua", b"print('b')")]);
+
+	let newest = SystemTime::UNIX_EPOCH + Duration::from_secs(1_500_000_000);
+	for (path, mtime) in [
+		("addon.json", newest - Duration::from_secs(20)),
+		("lua/autorun/a.lua", newest - Duration::from_secs(10)),
+		("lua/autorun/b.lua", newest),
+	] {
+		File::options()
+			.write(true)
+			.open(addon_dir.join(path))
+			.unwrap()
+			.set_modified(mtime)
+			.unwrap();
+	}
+
+	config.timestamp_from_files = true;
+	let gma_path = addon_dir.with_extension("gma");
+	fastgmad::create::create_gma(&config, &mut BufWriter::new(File::create(&gma_path).unwrap())).unwrap();
+
+	let mut config = test_extract_config();
+	config.restore_mtime = true;
+	#[cfg(unix)]
+	{
+		config.file_mode = Some(0o640);
+	}
+	fastgmad::extract::extract_gma(&config, &mut BufReader::new(File::open(&gma_path).unwrap())).unwrap();
+
+	for path in ["lua/autorun/a.lua", "lua/autorun/b.lua"] {
+		let metadata = std::fs::metadata(config.out.join(path)).unwrap();
+		assert_eq!(metadata.modified().unwrap(), newest);
+
+		#[cfg(unix)]
+		{
+			use std::os::unix::fs::PermissionsExt;
+			assert_eq!(metadata.permissions().mode() & 0o7777, 0o640);
+		}
+	}
+}
+
+#[cfg(unix)]
+#[test]
+fn test_extract_portable_names() {
+	use fastgmad::extract::PortableNames;
+
+	let addon_dir = create_test_addon(&[
+		("lua/autorun/a.lua", b"print('a')"),
+		("lua/con.lua", b"print('con')"),
+		("lua/a:b.lua", b"print('a:b')"),
+	]);
+	let gma = create_test_gma(&addon_dir);
+
+	let extract = |portable_names| {
+		let mut config = test_extract_config();
+		config.portable_names = portable_names;
+		fastgmad::extract::extract_gma(&config, &mut BufReader::new(File::open(&gma).unwrap())).map(|_| config.out)
+	};
+
+	// Nothing is written, not even the output folder
+	let mut config = test_extract_config();
+	config.portable_names = PortableNames::Fail;
+	let err = fastgmad::extract::extract_gma(&config, &mut BufReader::new(File::open(&gma).unwrap())).unwrap_err();
+	assert!(matches!(err.kind, fastgmad::error::FastGmadErrorKind::NonPortableFileName(_)));
+	assert!(!config.out.exists());
+
+	let out = extract(PortableNames::Skip).unwrap();
+	assert!(out.join("lua/autorun/a.lua").is_file());
+	assert!(!out.join("lua/con.lua").exists());
+	assert!(!out.join("lua/a:b.lua").exists());
+
+	let out = extract(PortableNames::Escape).unwrap();
+	assert_eq!(std::fs::read(out.join("lua/%63on.lua")).unwrap(), b"print('con')");
+	assert_eq!(std::fs::read(out.join("lua/a%3Ab.lua")).unwrap(), b"print('a:b')");
+
+	// Recreating the GMA from the escaped files restores the original names
+	let gma = create_test_gma(&out);
+	let config = test_extract_config();
+	fastgmad::extract::extract_gma(&config, &mut BufReader::new(File::open(&gma).unwrap())).unwrap();
+	assert_eq!(std::fs::read(config.out.join("lua/con.lua")).unwrap(), b"print('con')");
+	assert_eq!(std::fs::read(config.out.join("lua/a:b.lua")).unwrap(), b"print('a:b')");
+	assert!(!config.out.join(".fastgmad-escaped-names.json").exists());
+
+	// An escaped name can't overwrite an entry that already has that name
+	let addon_dir = create_test_addon(&[("lua/con.lua", b"print('con')"), ("lua/%63on.lua", b"print('%63on')")]);
+	let gma = create_test_gma(&addon_dir);
+	let mut config = test_extract_config();
+	config.portable_names = PortableNames::Escape;
+	let err = fastgmad::extract::extract_gma(&config, &mut BufReader::new(File::open(&gma).unwrap())).unwrap_err();
+	assert!(matches!(err.kind, fastgmad::error::FastGmadErrorKind::EscapedNameCollision(..)));
+	assert!(!config.out.exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_create_case_collisions() {
+	let (_, config) = test_addon(&[("materials/Foo.vmt", b"a"), ("materials/foo.vmt", b"b")]);
+	let err = fastgmad::create::create_gma(&config, &mut Vec::new()).unwrap_err();
+	assert!(matches!(err.kind, fastgmad::error::FastGmadErrorKind::CaseInsensitiveCollision(..)));
+
+	// Upper case paths are warned about, up to a limit
+	let files = (0..12).map(|i| (format!("materials/Foo{i:02}.vmt"), b"a".as_slice())).collect::<Vec<_>>();
+	let (_, config) = test_addon(
+		&files
+			.iter()
+			.map(|(path, contents)| (path.as_str(), *contents))
+			.chain(std::iter::once(("lua/weapons/gmod_tool/stools/a.lua", b"b".as_slice())))
+			.collect::<Vec<_>>(),
+	);
+	let (result, logs) = capture_logs(|| fastgmad::create::create_gma(&config, &mut Vec::new()));
+	result.unwrap();
+	assert_eq!(logs.len(), 12);
+	assert_eq!(
+		logs[0],
+		"File materials/Foo00.vmt has upper case characters in its path, which can break on case-sensitive file systems (Linux servers)"
+	);
+	assert_eq!(logs[10], "...and 2 more files with upper case characters in their paths");
+	assert_eq!(logs[11], "Use -lowercase to convert all paths in the GMA to lower case");
+
+	let (addon_dir, mut config) = test_addon(&[("materials/Foo/Bar.vmt", b"a"), ("lua/autorun/a.lua", b"b")]);
+	config.lowercase = true;
+	let gma_path = addon_dir.with_extension("gma");
+	fastgmad::create::create_gma(&config, &mut BufWriter::new(File::create(&gma_path).unwrap())).unwrap();
+
+	let config = test_extract_config();
+	fastgmad::extract::extract_gma(&config, &mut BufReader::new(File::open(&gma_path).unwrap())).unwrap();
+	assert!(config.out.join("materials/foo/bar.vmt").is_file());
+}
+
+#[test]
+fn test_extract_parallel_many_small_files() {
+	let files = (0..2000)
+		.map(|i| (format!("lua/many/{}/{i}.lua", i % 16), format!("print({i})")))
+		.collect::<Vec<_>>();
+	let addon_dir = create_test_addon(
+		&files
+			.iter()
+			.map(|(path, contents)| (path.as_str(), contents.as_bytes()))
+			.collect::<Vec<_>>(),
+	);
+	let gma = create_test_gma(&addon_dir);
+
+	let mut config = test_extract_config();
+	config.max_io_threads = NonZeroUsize::new(4).unwrap();
+	config.max_io_memory_usage = NonZeroUsize::new(64).unwrap(); // force back-pressure
+	fastgmad::extract::extract_gma(&config, &mut BufReader::new(File::open(&gma).unwrap())).unwrap();
+
+	for (path, contents) in files {
+		assert_eq!(std::fs::read_to_string(config.out.join(path)).unwrap(), contents);
+	}
+}
+
+#[test]
+fn test_extract_from_file_parallel_reads() {
+	let big = (0..100_000u32).flat_map(|i| i.to_le_bytes()).collect::<Vec<_>>();
+	let mut files = (0..200)
+		.map(|i| (format!("lua/small/{i}.lua"), format!("print({i})").into_bytes()))
+		.collect::<Vec<_>>();
+	files.push(("materials/big.vtf".to_string(), big));
+	let addon_dir = create_test_addon(
+		&files
+			.iter()
+			.map(|(path, contents)| (path.as_str(), contents.as_slice()))
+			.collect::<Vec<_>>(),
+	);
+	let gma = create_test_gma(&addon_dir);
+
+	let mut config = test_extract_config();
+	config.max_io_threads = NonZeroUsize::new(4).unwrap();
+	config.max_io_memory_usage = NonZeroUsize::new(4096).unwrap(); // stream the big entry in chunks
+
+	let mut r = BufReader::new(File::open(&gma).unwrap());
+	fastgmad::extract::extract_gma_from_file(&config, &mut r).unwrap();
+	assert_eq!(r.stream_position().unwrap(), std::fs::metadata(&gma).unwrap().len());
+
+	for (path, contents) in files {
+		assert_eq!(std::fs::read(config.out.join(path)).unwrap(), contents);
+	}
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_kernel_copy() {
+	let dir = GMA_TEMP_DIR.join(Uuid::new_v4().to_string());
+	std::fs::create_dir_all(&dir).unwrap();
+
+	let src_path = dir.join("src");
+	std::fs::write(&src_path, b"0123456789").unwrap();
+	let src = File::open(&src_path).unwrap();
+
+	let dst_path = dir.join("dst");
+	let dst = File::create(&dst_path).unwrap();
+	let copied = crate::util::kernel_copy(&src, 2, &dst, 5).unwrap();
+	// Past the end of the source file
+	let copied_eof = crate::util::kernel_copy(&src, 8, &dst, 5).unwrap();
+	drop(dst);
+
+	assert_eq!((copied, copied_eof), (5, 2));
+	assert_eq!(std::fs::read(&dst_path).unwrap(), b"2345689");
+}
+
+#[test]
+fn test_file_copies() {
+	let big = vec![7u8; 3 * 1024 * 1024];
+	let files: &[(&str, &[u8])] = &[("lua/autorun/a.lua", b"print('a')"), ("materials/big.vtf", &big), ("sound/b.wav", b"wav")];
+	let addon_dir = create_test_addon(files);
+
+	for threads in [1, 2] {
+		let config = CreateGmaConfig {
+			folder: addon_dir.clone(),
+			max_io_threads: NonZeroUsize::new(threads).unwrap(),
+			max_io_memory_usage: NonZeroUsize::new(1024 * 1024).unwrap(), // force the big entry to be copied
+			..Default::default()
+		};
+
+		let mut expected = Vec::new();
+		fastgmad::create::create_gma(&config, &mut expected).unwrap();
+
+		let gma_path = GMA_TEMP_DIR.join(Uuid::new_v4().to_string()).with_extension("gma");
+		let mut w = BufWriter::new(File::create(&gma_path).unwrap());
+		fastgmad::create::create_gma_to_file(&config, &mut w).unwrap();
+		drop(w.into_inner().unwrap());
+		assert!(std::fs::read(&gma_path).unwrap() == expected, "{threads} threads");
+
+		let mut extract_config = test_extract_config();
+		extract_config.max_io_threads = NonZeroUsize::new(threads).unwrap();
+		let mut r = BufReader::new(File::open(&gma_path).unwrap());
+		fastgmad::extract::extract_gma_from_file(&extract_config, &mut r).unwrap();
+		for (path, contents) in files {
+			assert!(std::fs::read(extract_config.out.join(path)).unwrap() == *contents, "{path}");
+		}
+	}
+}
+
+#[test]
+#[cfg(feature = "mmap")]
+fn test_mmap_gma() {
+	use std::io::Write;
+
+	let files: &[(&str, &[u8])] = &[("lua/autorun/a.lua", b"print('a')"), ("materials/b.vmt", b"\"VertexLitGeneric\" {}")];
+	let addon_dir = create_test_addon(files);
+	let gma_path = create_test_gma(&addon_dir);
+
+	let gma = fastgmad::mmap::MmapGma::open(&gma_path).unwrap();
+	assert_eq!(gma.title(), b"Test Addon");
+	assert_eq!(gma.len(), files.len());
+	for (path, contents) in files {
+		assert_eq!(gma.entry(path.as_bytes()).unwrap().contents, *contents);
+	}
+
+	let mut config = test_extract_config();
+	config.max_io_threads = NonZeroUsize::new(2).unwrap();
+	fastgmad::extract::extract_mmap_gma(&config, &gma).unwrap();
+	for (path, contents) in files {
+		assert_eq!(std::fs::read(config.out.join(path)).unwrap(), *contents);
+	}
+
+	// Another process appending to the file behind our back
+	OpenOptions::new().append(true).open(&gma_path).unwrap().write_all(b"garbage").unwrap();
+	assert!(matches!(
+		gma.verify(),
+		Err(fastgmad::error::FastGmadError {
+			kind: fastgmad::error::FastGmadErrorKind::MappedFileModified(_),
+			..
+		})
+	));
+}
+
+#[test]
+fn test_create_pipelined() {
+	let files = (0..500)
+		.map(|i| (format!("lua/pipeline/{i}.lua"), format!("print({i})").into_bytes()))
+		.chain(std::iter::once(("materials/big.vtf".to_string(), vec![b'x'; 1024])))
+		.collect::<Vec<_>>();
+	let (_, mut config) = test_addon(
+		&files
+			.iter()
+			.map(|(path, contents)| (path.as_str(), contents.as_slice()))
+			.collect::<Vec<_>>(),
+	);
+
+	config.timestamp_from_files = true;
+	config.max_io_threads = NonZeroUsize::new(1).unwrap(); // force series
+	let mut standard = Vec::new();
+	fastgmad::create::create_gma(&config, &mut standard).unwrap();
+
+	config.max_io_threads = NonZeroUsize::new(4).unwrap();
+	config.max_io_memory_usage = NonZeroUsize::new(64).unwrap(); // force back-pressure, and copy the big file directly
+	let mut pipelined = Vec::new();
+	fastgmad::create::create_gma(&config, &mut pipelined).unwrap();
+
+	assert!(standard == pipelined);
+}
+
+#[test]
+fn test_extract_gma_stream() {
+	let addons = [
+		test_addon(&[("lua/autorun/a.lua", b"print('a')")]).1,
+		test_addon(&[("lua/autorun/b.lua", b"print('b')")]).1,
+	];
+
+	let mut stream = Vec::new();
+	for config in &addons {
+		fastgmad::create::create_gma(config, &mut stream).unwrap();
+	}
+
+	let mut config = test_extract_config();
+	let outs = fastgmad::extract::extract_gma_stream(&config, &mut stream.as_slice()).unwrap();
+
+	// Both addons have the same title
+	assert_eq!(outs, [config.out.join("Test Addon"), config.out.join("2")]);
+	assert_eq!(std::fs::read(outs[0].join("lua/autorun/a.lua")).unwrap(), b"print('a')");
+	assert_eq!(std::fs::read(outs[1].join("lua/autorun/b.lua")).unwrap(), b"print('b')");
+
+	// Truncated streams are an error rather than a partial GMA
+	stream.truncate(stream.len() - 2);
+	config.out = GMA_TEMP_DIR.join(Uuid::new_v4().to_string());
+	fastgmad::extract::extract_gma_stream(&config, &mut stream.as_slice()).unwrap_err();
+}
+
+#[test]
+fn test_create_ignore_files() {
+	let addon_dir = create_test_addon(&[
+		("lua/autorun/a.lua", b"print('a')"),
+		("lua/autorun/b.lua", b"print('b')"),
+		("lua/autorun/keep.lua", b"print('keep')"),
+		("lua/build/out.lua", b"print('out')"),
+		("lua/dev/debug.lua", b"print('debug')"),
+		("materials/test.png", b"png"),
+		(".gmadignore", b"# comment\nlua/dev/\n"),
+		("lua/.gitignore", b"build/\n"),
+		("lua/autorun/.gmadignore", b"*.lua\n!keep.lua\n"),
+		("materials/.gmadignore", b"!test.png\n"),
+	]);
+	std::fs::write(
+		addon_dir.join("addon.json"),
+		br#"{"title": "Test Addon", "type": "tool", "tags": ["fun"], "ignore": ["*.png"]}"#,
+	)
+	.unwrap();
+
+	let packed = |respect_gitignore: bool| {
+		let config = CreateGmaConfig {
+			folder: addon_dir.clone(),
+			respect_gitignore,
+			..Default::default()
+		};
+		packed_files(&config).1
+	};
+
+	assert_eq!(packed(true), ["lua/autorun/keep.lua", "materials/test.png"]);
+	assert_eq!(packed(false), ["lua/autorun/keep.lua", "lua/build/out.lua", "materials/test.png"]);
+}
+
+/// Creates a .GMA and returns the addon.json and paths of the files packed into it
+fn packed_files(config: &CreateGmaConfig) -> (serde_json::Value, Vec<String>) {
+	let mut gma = Vec::new();
+	fastgmad::create::create_gma(config, &mut gma).unwrap();
+
+	let extract_config = test_extract_config();
+	fastgmad::extract::extract_gma(&extract_config, &mut gma.as_slice()).unwrap();
+
+	let addon_json = serde_json::from_slice(&std::fs::read(extract_config.out.join("addon.json")).unwrap()).unwrap();
+
+	let mut packed = walkdir::WalkDir::new(&extract_config.out)
+		.into_iter()
+		.map(|entry| entry.unwrap())
+		.filter(|entry| entry.file_type().is_file())
+		.map(|entry| {
+			entry
+				.path()
+				.strip_prefix(&extract_config.out)
+				.unwrap()
+				.to_str()
+				.unwrap()
+				.replace('\\', "/")
+		})
+		.filter(|path| path != "addon.json")
+		.collect::<Vec<_>>();
+	packed.sort();
+
+	(addon_json, packed)
+}
+
+#[test]
+fn test_create_profiles() {
+	let (addon_dir, mut config) = test_addon(&[
+		("lua/autorun/client/a.lua", b"print('a')"),
+		("lua/autorun/server/b.lua", b"print('b')"),
+		("materials/big.vtf", b"vtf"),
+		("materials/small.png", b"png"),
+	]);
+	std::fs::write(
+		addon_dir.join("addon.json"),
+		br#"{
+			"title": "Test Addon",
+			"type": "tool",
+			"tags": ["fun"],
+			"include": ["lua/", "materials/*.png"],
+			"profiles": {
+				"content": { "title": "Test Addon Content", "include": ["materials/"], "tags": ["build"] },
+				"server": { "ignore": ["lua/autorun/client/"] }
+			}
+		}"#,
+	)
+	.unwrap();
+
+	config.ignore_syntax = fastgmad::ignore::IgnoreSyntax::Gitignore;
+
+	let (addon_json, packed) = packed_files(&config);
+	assert_eq!(packed, ["lua/autorun/client/a.lua", "lua/autorun/server/b.lua", "materials/small.png"]);
+	assert_eq!(addon_json, serde_json::json!({ "title": "Test Addon", "type": "tool", "tags": ["fun"] }));
+
+	config.profile = Some("content".to_string());
+	let (addon_json, packed) = packed_files(&config);
+	assert_eq!(packed, ["materials/big.vtf", "materials/small.png"]);
+	assert_eq!(addon_json["title"], "Test Addon Content");
+	assert_eq!(addon_json["tags"], serde_json::json!(["build"]));
+	assert_eq!(addon_json["type"], "tool");
+	assert!(addon_json.get("profiles").is_none());
+	assert!(addon_json.get("include").is_none());
+
+	config.profile = Some("server".to_string());
+	let (addon_json, packed) = packed_files(&config);
+	assert_eq!(packed, ["lua/autorun/server/b.lua", "materials/small.png"]);
+	assert_eq!(
+		addon_json,
+		serde_json::json!({ "title": "Test Addon", "type": "tool", "tags": ["fun"], "ignore": ["lua/autorun/client/"] })
+	);
+
+	config.profile = Some("missing".to_string());
+	assert!(fastgmad::create::create_gma(&config, &mut Vec::new()).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_create_hidden_files_and_symlinks() {
+	use fastgmad::create::{HiddenFiles, Symlinks};
+
+	let (addon_dir, mut config) = test_addon(&[("lua/autorun/a.lua", b"print('a')"), ("lua/autorun/.secret.lua", b"print('secret')")]);
+
+	let outside = GMA_TEMP_DIR.join(format!("{}.lua", Uuid::new_v4()));
+	std::fs::write(&outside, b"print('outside')").unwrap();
+	std::os::unix::fs::symlink(&outside, addon_dir.join("lua/outside.lua")).unwrap();
+	std::os::unix::fs::symlink(addon_dir.join("lua/autorun"), addon_dir.join("lua/inside")).unwrap();
+
+	assert_eq!(packed_files(&config).1, ["lua/autorun/a.lua", "lua/inside/a.lua", "lua/outside.lua"]);
+
+	config.hidden_files = HiddenFiles::Include;
+	assert_eq!(
+		packed_files(&config).1,
+		[
+			"lua/autorun/.secret.lua",
+			"lua/autorun/a.lua",
+			"lua/inside/.secret.lua",
+			"lua/inside/a.lua",
+			"lua/outside.lua"
+		]
+	);
+
+	config.hidden_files = HiddenFiles::Error;
+	assert!(fastgmad::create::create_gma(&config, &mut Vec::new()).is_err());
+	config.hidden_files = HiddenFiles::Skip;
+
+	config.symlinks = Symlinks::Skip;
+	assert_eq!(packed_files(&config).1, ["lua/autorun/a.lua"]);
+
+	config.symlinks = Symlinks::Error;
+	assert!(fastgmad::create::create_gma(&config, &mut Vec::new()).is_err());
+
+	config.symlinks = Symlinks::FollowWithinRoot;
+	assert!(fastgmad::create::create_gma(&config, &mut Vec::new()).is_err());
+	std::fs::remove_file(addon_dir.join("lua/outside.lua")).unwrap();
+	assert_eq!(packed_files(&config).1, ["lua/autorun/a.lua", "lua/inside/a.lua"]);
+}
+
+#[test]
+fn test_create_reports_every_whitelist_violation() {
+	let (_, mut config) = test_addon(&[
+		("lua/autorun/a.lua", b"print('a')"),
+		("sounds/test.wav", b"wav"),
+		("lua/autorun/b.exe", b"exe"),
+		("README.md", b"readme"),
+	]);
+
+	let error = fastgmad::create::create_gma(&config, &mut Vec::new()).unwrap_err();
+	match error.kind {
+		fastgmad::error::FastGmadErrorKind::EntriesNotWhitelisted(violations) => {
+			assert_eq!(
+				violations.iter().map(|violation| violation.path.as_str()).collect::<Vec<_>>(),
+				["README.md", "lua/autorun/b.exe", "sounds/test.wav"]
+			);
+			assert_eq!(violations[0].suggestion, None);
+			assert!(violations[1].suggestion.is_some());
+			assert_eq!(violations[2].suggestion.as_deref(), Some("did you mean sound/test.wav?"));
+		}
+		kind => panic!("unexpected error {kind}"),
+	}
+
+	config.warn_invalid = true;
+	assert_eq!(packed_files(&config).1, ["lua/autorun/a.lua"]);
+}
+
+#[test]
+fn test_create_extra_whitelist() {
+	let (addon_dir, mut config) = test_addon(&[("lua/autorun/a.lua", b"print('a')"), ("data/test.txt", b"test")]);
+	let whitelist_path = addon_dir.with_extension("json");
+	std::fs::write(&whitelist_path, br#"{ "version": "private-1", "whitelist": ["data/*.txt"] }"#).unwrap();
+
+	assert!(fastgmad::create::create_gma(&config, &mut Vec::new()).is_err());
+
+	config.whitelist_files.push(whitelist_path);
+	assert_eq!(packed_files(&config).1, ["data/test.txt", "lua/autorun/a.lua"]);
+}
+
+#[test]
+fn test_create_invalid_addon_json() {
+	let (addon_dir, mut config) = test_addon(&[("lua/weapons/gmod_tool/stools/a.lua", b"print('a')")]);
+	std::fs::write(
+		addon_dir.join("addon.json"),
+		r#"{"title": "Test Addon", "type": "toolz", "tags": ["fun", "build", "water"]}"#,
+	)
+	.unwrap();
+
+	let err = fastgmad::create::create_gma(&config, &mut Vec::new()).unwrap_err();
+	assert!(matches!(err.kind, fastgmad::error::FastGmadErrorKind::InvalidAddonJson(..)));
+
+	// gmad packs these anyway, so -warninvalid does too
+	config.warn_invalid = true;
+	let (result, logs) = capture_logs(|| fastgmad::create::create_gma(&config, &mut Vec::new()));
+	result.unwrap();
+	assert_eq!(
+		logs[..2],
+		[
+			"addon.json line 1 column 33: \"type\" is \"toolz\", but must be one of gamemode, map, weapon, vehicle, npc, entity, tool, effects, model, servercontent",
+			"addon.json line 1 column 50: \"tags\" has 3 tags, but an addon can have at most 2"
+		]
+	);
+}
+
+#[test]
+fn test_suggest_addon() {
+	let (addon_dir, config) = test_addon(&[("maps/gm_test.bsp", b"bsp"), ("materials/test.vmt", b"vmt"), ("maps/old.bsp", b"bsp")]);
+	std::fs::write(
+		addon_dir.join("addon.json"),
+		"{\n\t\"title\": \"Test Addon\",\n\t\"type\": \"weapons\",\n\t\"ignore\": [\"maps/old.bsp\"]\n}\n",
+	)
+	.unwrap();
+
+	assert!(fastgmad::create::create_gma(&config, &mut Vec::new()).is_err());
+
+	let suggestion = fastgmad::create::suggest_addon(&config).unwrap();
+	assert_eq!(suggestion.addon_type().unwrap().to_string(), "map (maps/gm_test.bsp)");
+	assert_eq!(suggestion.tags.iter().map(|tag| tag.value).collect::<Vec<_>>(), ["scenic"]);
+
+	fastgmad::create::write_suggestion(&config, &suggestion).unwrap();
+	assert_eq!(
+		std::fs::read_to_string(addon_dir.join("addon.json")).unwrap(),
+		"{\n\t\"title\": \"Test Addon\",\n\t\"type\": \"map\",\n\t\"ignore\": [\"maps/old.bsp\"],\n\t\"tags\": [\"scenic\"]\n}\n"
+	);
+	assert_eq!(packed_files(&config).1, ["maps/gm_test.bsp", "materials/test.vmt"]);
+
+	// Tags already in addon.json are kept, and suggestions only fill the free slots
+	std::fs::write(
+		addon_dir.join("addon.json"),
+		"{ \"title\": \"Test Addon\", \"type\": \"map\", \"tags\": [\"fun\"] }",
+	)
+	.unwrap();
+	fastgmad::create::write_suggestion(&config, &suggestion).unwrap();
+	assert_eq!(
+		std::fs::read_to_string(addon_dir.join("addon.json")).unwrap(),
+		"{ \"title\": \"Test Addon\", \"type\": \"map\", \"tags\": [\"fun\",\"scenic\"] }"
+	);
+
+	let full = "{ \"title\": \"Test Addon\", \"type\": \"map\", \"tags\": [ \"fun\", \"build\" ] }";
+	std::fs::write(addon_dir.join("addon.json"), full).unwrap();
+	fastgmad::create::write_suggestion(&config, &suggestion).unwrap();
+	assert_eq!(std::fs::read_to_string(addon_dir.join("addon.json")).unwrap(), full);
+
+	std::fs::remove_file(addon_dir.join("addon.json")).unwrap();
+	fastgmad::create::write_suggestion(&config, &suggestion).unwrap();
+	let addon_json = std::fs::read_to_string(addon_dir.join("addon.json")).unwrap();
+	let addon_json = serde_json::from_str::<serde_json::Value>(&addon_json).unwrap();
+	assert_eq!(addon_json["title"], addon_dir.file_name().unwrap().to_str().unwrap());
+	assert_eq!(addon_json["type"], "map");
+}
+
+#[test]
+#[cfg(unix)]
+fn test_create_normalizes_paths() {
+	use std::os::unix::ffi::OsStrExt;
+
+	let (addon_dir, mut config) = test_addon(&[("sound/cafe\u{301}.wav", b"wav"), ("lua/autorun/a.lua", b"print('a')")]);
+
+	assert_eq!(packed_files(&config).1, ["lua/autorun/a.lua", "sound/caf\u{e9}.wav"]);
+
+	std::fs::write(addon_dir.join("lua/autorun/b\tc.lua"), b"print('b')").unwrap();
+	std::fs::write(addon_dir.join(std::ffi::OsStr::from_bytes(b"lua/autorun/caf\xe9.lua")), b"print('c')").unwrap();
+	let error = fastgmad::create::create_gma(&config, &mut Vec::new()).unwrap_err();
+	match error.kind {
+		fastgmad::error::FastGmadErrorKind::UnrepresentablePaths(paths) => {
+			assert_eq!(
+				paths.iter().map(ToString::to_string).collect::<Vec<_>>(),
+				[
+					"lua/autorun/b\\tc.lua contains the control character U+0009",
+					"lua/autorun/caf\u{fffd}.lua isn't valid UTF-8. Use -non-utf8-names lossy or escape to pack it anyway"
+				]
+			);
+		}
+		kind => panic!("unexpected error {kind}"),
+	}
+
+	config.warn_invalid = true;
+	assert_eq!(packed_files(&config).1, ["lua/autorun/a.lua", "sound/caf\u{e9}.wav"]);
+
+	config.non_utf8_names = fastgmad::create::NonUtf8Names::Escape;
+	assert_eq!(
+		packed_files(&config).1,
+		["lua/autorun/a.lua", "lua/autorun/caf%E9.lua", "sound/caf\u{e9}.wav"]
+	);
+}
+
+/// Builds a .GMA by hand, for entries that fastgmad would never create
+fn raw_gma(entries: &[(&[u8], &[u8])]) -> Vec<u8> {
+	let mut gma = b"GMAD\x03".to_vec();
+	gma.extend_from_slice(&[0; 8]); // SteamID
+	gma.extend_from_slice(&0u64.to_le_bytes()); // Timestamp
+	gma.push(0); // Required content
+	gma.extend_from_slice(b"Test Addon\0{\"title\": \"Test Addon\"}\0\0");
+	gma.extend_from_slice(&1u32.to_le_bytes()); // Addon version
+	for (i, (path, contents)) in entries.iter().enumerate() {
+		gma.extend_from_slice(&(i as u32 + 1).to_le_bytes());
+		gma.extend_from_slice(path);
+		gma.push(0);
+		gma.extend_from_slice(&(contents.len() as i64).to_le_bytes());
+		gma.extend_from_slice(&[0; 4]); // CRC
+	}
+	gma.extend_from_slice(&[0; 4]);
+	for (_, contents) in entries {
+		gma.extend_from_slice(contents);
+	}
+	gma
+}
+
+#[test]
+fn test_extract_skips_absolute_paths() {
+	let outside = GMA_TEMP_DIR.join(format!("{}.lua", Uuid::new_v4()));
+	let gma = raw_gma(&[
+		(outside.to_str().unwrap().as_bytes(), b"print('outside')"),
+		(b"lua/autorun/a.lua", b"print('a')"),
+	]);
+
+	let config = test_extract_config();
+	fastgmad::extract::extract_gma(&config, &mut gma.as_slice()).unwrap();
+
+	assert!(!outside.exists());
+	assert_eq!(std::fs::read(config.out.join("lua/autorun/a.lua")).unwrap(), b"print('a')");
+}
+
+/// Runs `f`, returning the warnings it logged on this thread
+fn capture_logs<T>(f: impl FnOnce() -> T) -> (T, Vec<String>) {
+	thread_local! {
+		static CAPTURED: std::cell::RefCell<Option<Vec<String>>> = const { std::cell::RefCell::new(None) };
+	}
+
+	struct CaptureLogger;
+	impl log::Log for CaptureLogger {
+		fn enabled(&self, metadata: &log::Metadata) -> bool {
+			metadata.level() <= log::Level::Warn
+		}
+
+		fn log(&self, record: &log::Record) {
+			CAPTURED.with(|captured| {
+				if let Some(captured) = captured.borrow_mut().as_mut() {
+					captured.push(record.args().to_string());
+				}
+			});
+		}
+
+		fn flush(&self) {}
+	}
+
+	static INIT: std::sync::Once = std::sync::Once::new();
+	INIT.call_once(|| {
+		log::set_logger(&CaptureLogger).unwrap();
+		log::set_max_level(log::LevelFilter::Warn);
+	});
+
+	CAPTURED.with(|captured| *captured.borrow_mut() = Some(Vec::new()));
+	let result = f();
+	let logs = CAPTURED.with(|captured| captured.borrow_mut().take().unwrap());
+	(result, logs)
+}
+
+#[test]
+fn test_extract_path_encodings() {
+	let gma = raw_gma(&[
+		(b"lua/caf\xe9.lua", b"print('cafe')"),
+		("lua/привет.lua".as_bytes(), b"print('privet')"),
+		(b"lua/\xef\xf0\xe8\xe2\xe5\xf2.lua", b"print('cp1251')"),
+	]);
+
+	let mut config = test_extract_config();
+	let (result, logs) = capture_logs(|| fastgmad::extract::extract_gma(&config, &mut gma.as_slice()));
+	result.unwrap();
+	assert_eq!(std::fs::read(config.out.join("lua/café.lua")).unwrap(), b"print('cafe')");
+	assert_eq!(std::fs::read(config.out.join("lua/привет.lua")).unwrap(), b"print('privet')");
+	assert_eq!(std::fs::read(config.out.join("lua/ïðèâåò.lua")).unwrap(), b"print('cp1251')");
+	let summary = logs.iter().position(|log| log.contains("as Windows-1252")).unwrap();
+	assert_eq!(
+		logs[summary + 1..summary + 3],
+		["    lua/caf\u{fffd}.lua", "    lua/\u{fffd}\u{fffd}\u{fffd}\u{fffd}\u{fffd}\u{fffd}.lua"]
+	);
+
+	config.out = GMA_TEMP_DIR.join(Uuid::new_v4().to_string());
+	config.path_encoding = fastgmad::extract::PathEncoding::Utf8;
+	let (result, logs) = capture_logs(|| fastgmad::extract::extract_gma(&config, &mut gma.as_slice()));
+	result.unwrap();
+	assert!(!config.out.join("lua/café.lua").exists());
+	assert_eq!(std::fs::read(config.out.join("lua/привет.lua")).unwrap(), b"print('privet')");
+	let summary = logs
+		.iter()
+		.position(|log| log.starts_with("Skipped 2 GMA entries whose file paths could not be decoded as utf8"))
+		.unwrap();
+	assert_eq!(logs[summary + 1], "    lua/caf\u{fffd}.lua");
+
+	config.out = GMA_TEMP_DIR.join(Uuid::new_v4().to_string());
+	config.path_encoding = "cp1251".parse().unwrap();
+	let (result, logs) = capture_logs(|| fastgmad::extract::extract_gma(&config, &mut gma.as_slice()));
+	result.unwrap();
+	assert_eq!(std::fs::read(config.out.join("lua/привет.lua")).unwrap(), b"print('cp1251')");
+	assert!(!logs
+		.iter()
+		.any(|log| log.contains("Windows-1252") || log.contains("could not be decoded")));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_create_path_collisions() {
+	use std::os::unix::ffi::OsStrExt;
+
+	fn path_collision(config: &CreateGmaConfig) -> fastgmad::create::PathCollision {
+		match fastgmad::create::create_gma(config, &mut Vec::new()).unwrap_err().kind {
+			fastgmad::error::FastGmadErrorKind::PathCollision(collision) => collision,
+			kind => panic!("unexpected error {kind}"),
+		}
+	}
+
+	let (addon_dir, mut config) = test_addon(&[("lua/autorun/caf%E9.lua", b"print('a')")]);
+	std::fs::write(addon_dir.join(std::ffi::OsStr::from_bytes(b"lua/autorun/caf\xe9.lua")), b"print('b')").unwrap();
+
+	config.non_utf8_names = fastgmad::create::NonUtf8Names::Escape;
+	let collision = path_collision(&config);
+	let mut paths = collision.paths.clone();
+	paths.sort();
+	assert_eq!(paths, ["lua/autorun/caf%E9.lua", "lua/autorun/caf\u{fffd}.lua"]);
+	assert_eq!(collision.path, "lua/autorun/caf%E9.lua");
+	assert_eq!(
+		collision.reason,
+		fastgmad::create::RenameReason::NotUtf8(fastgmad::create::NonUtf8Names::Escape)
+	);
+	assert!(collision.to_string().contains("-non-utf8-names escape"));
+
+	std::fs::write(addon_dir.join("lua/autorun/caf\u{fffd}.lua"), b"print('c')").unwrap();
+	config.non_utf8_names = fastgmad::create::NonUtf8Names::Lossy;
+	let collision = path_collision(&config);
+	assert_eq!(collision.path, "lua/autorun/caf\u{fffd}.lua");
+	assert_eq!(
+		collision.reason,
+		fastgmad::create::RenameReason::NotUtf8(fastgmad::create::NonUtf8Names::Lossy)
+	);
+
+	let addon_dir = create_test_addon(&[("sound/caf\u{e9}.wav", b"nfc"), ("sound/cafe\u{301}.wav", b"nfd")]);
+	config.folder = addon_dir;
+	let collision = path_collision(&config);
+	let mut paths = collision.paths.clone();
+	paths.sort();
+	assert_eq!(paths, ["sound/cafe\u{301}.wav", "sound/caf\u{e9}.wav"]);
+	assert_eq!(collision.path, "sound/caf\u{e9}.wav");
+	assert_eq!(collision.reason, fastgmad::create::RenameReason::Normalized);
+	assert!(collision.to_string().contains("Unicode NFC"));
+}
@@ -1,10 +1,10 @@
 use std::{
 	fs::File,
-	io::{BufRead, BufReader, Seek, SeekFrom, StdinLock, Write},
+	io::{BufRead, BufReader, Read, Seek, SeekFrom, StdinLock, Write},
 	path::Path,
 };
 
-pub fn is_hidden_file(path: &Path) -> Result<bool, std::io::Error> {
+pub fn is_hidden_file(path: &Path, #[allow(unused_variables)] metadata: &std::fs::Metadata) -> bool {
 	let hidden;
 
 	#[cfg(unix)]
@@ -21,10 +21,85 @@ pub fn is_hidden_file(path: &Path) -> Result<bool, std::io::Error> {
 	{
 		use std::os::windows::fs::MetadataExt;
 		const HIDDEN: u32 = 0x00000002;
-		hidden = std::fs::metadata(path)?.file_attributes() & HIDDEN != 0;
+		hidden = metadata.file_attributes() & HIDDEN != 0;
 	}
 
-	Ok(hidden)
+	hidden
+}
+
+/// Reads exactly `buf.len()` bytes from `f` at `offset`
+#[cfg(unix)]
+pub fn read_exact_at(f: &File, buf: &mut [u8], offset: u64) -> Result<(), std::io::Error> {
+	use std::os::unix::fs::FileExt;
+	f.read_exact_at(buf, offset)
+}
+
+/// Reads exactly `buf.len()` bytes from `f` at `offset`
+///
+/// Unlike on Unix, this moves the file cursor.
+#[cfg(windows)]
+pub fn read_exact_at(f: &File, mut buf: &mut [u8], mut offset: u64) -> Result<(), std::io::Error> {
+	use std::os::windows::fs::FileExt;
+	while !buf.is_empty() {
+		match f.seek_read(buf, offset) {
+			Ok(0) => return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "failed to fill whole buffer")),
+			Ok(read) => {
+				buf = &mut buf[read..];
+				offset += read as u64;
+			}
+			Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+			Err(e) => return Err(e),
+		}
+	}
+	Ok(())
+}
+
+/// Copies shorter than this go through userspace, where they're cheaper than a [`kernel_copy`] call and the writer flush it needs
+pub const KERNEL_COPY_MIN_LEN: u64 = 64 * 1024;
+
+/// Copies up to `len` bytes from `src` at `src_offset` to `dst` at its cursor without going through userspace,
+/// using `copy_file_range` (which can also reflink on btrfs/XFS if the offsets are block aligned), or `sendfile` on older kernels
+///
+/// Returns how many bytes were copied, which is less than `len` if the kernel or file system can't copy between these files
+/// (or if `src` ends early), in which case the caller should copy the rest itself.
+///
+/// `std::io::copy` already does this for `File` to `File` copies, but can't copy from an offset without moving the source file's cursor.
+#[cfg(target_os = "linux")]
+pub fn kernel_copy(src: &File, src_offset: u64, dst: &File, len: u64) -> Result<u64, std::io::Error> {
+	use std::os::fd::AsRawFd;
+
+	let mut use_sendfile = false;
+	let mut copied = 0;
+	while copied < len {
+		let mut offset = (src_offset + copied) as libc::off64_t;
+		let chunk = (len - copied).min(0x7ffff000) as usize; // The most Linux will copy in one call
+		let res = unsafe {
+			if use_sendfile {
+				libc::sendfile64(dst.as_raw_fd(), src.as_raw_fd(), &mut offset, chunk)
+			} else {
+				libc::copy_file_range(src.as_raw_fd(), &mut offset, dst.as_raw_fd(), core::ptr::null_mut(), chunk, 0)
+			}
+		};
+		match res {
+			0 => break,
+			-1 => {
+				let error = std::io::Error::last_os_error();
+				match error.raw_os_error() {
+					Some(libc::EINTR) => continue,
+
+					// No copy_file_range (Linux < 4.5, seccomp), or not between these file systems
+					Some(libc::ENOSYS | libc::EPERM | libc::EXDEV | libc::EOPNOTSUPP | libc::EINVAL) if !use_sendfile => use_sendfile = true,
+
+					// The kernel or file system can't do this, copy through userspace
+					Some(libc::ENOSYS | libc::EPERM | libc::EXDEV | libc::EOPNOTSUPP | libc::EINVAL) => break,
+
+					_ => return Err(error),
+				}
+			}
+			n => copied += n as u64,
+		}
+	}
+	Ok(copied)
 }
 
 pub trait WriteEx: Write {
@@ -72,12 +147,22 @@ impl<R: BufRead> BufReadEx for R {
 	}
 
 	fn skip_nul_str(&mut self) -> Result<(), std::io::Error> {
-		self.skip_until(0).map(|_| ())
+		BufReadEx::skip_until(self, 0).map(|_| ())
 	}
 }
 
 pub trait IoSkip {
 	fn skip(&mut self, bytes: u64) -> Result<(), std::io::Error>;
+
+	/// Copies up to `len` bytes into `dst`, returning how many were copied
+	///
+	/// Readers that are files can override this to copy inside the kernel.
+	fn copy_to_file(&mut self, mut dst: &File, len: u64) -> Result<u64, std::io::Error>
+	where
+		Self: Read + Sized,
+	{
+		std::io::copy(&mut self.take(len), &mut dst)
+	}
 }
 impl IoSkip for File {
 	fn skip(&mut self, bytes: u64) -> Result<(), std::io::Error> {
@@ -92,6 +177,37 @@ impl IoSkip for BufReader<File> {
 		self.seek(SeekFrom::Start(pos + bytes))?;
 		Ok(())
 	}
+
+	fn copy_to_file(&mut self, mut dst: &File, len: u64) -> Result<u64, std::io::Error> {
+		#[cfg(target_os = "linux")]
+		let copied = if len >= KERNEL_COPY_MIN_LEN {
+			let pos = self.stream_position()?;
+			let copied = kernel_copy(self.get_ref(), pos, dst, len)?;
+			self.seek_relative(copied as i64)?;
+			copied
+		} else {
+			0
+		};
+		#[cfg(not(target_os = "linux"))]
+		let copied = 0;
+
+		// Copy whatever the kernel couldn't through userspace
+		Ok(copied + std::io::copy(&mut self.take(len - copied), &mut dst)?)
+	}
+}
+impl IoSkip for &[u8] {
+	fn skip(&mut self, bytes: u64) -> Result<(), std::io::Error> {
+		match usize::try_from(bytes).ok().and_then(|bytes| self.get(bytes..)) {
+			Some(rest) => {
+				*self = rest;
+				Ok(())
+			}
+			None => Err(std::io::Error::new(
+				std::io::ErrorKind::UnexpectedEof,
+				"failed to skip past end of buffer",
+			)),
+		}
+	}
 }
 impl IoSkip for StdinLock<'_> {
 	fn skip(&mut self, bytes: u64) -> Result<(), std::io::Error> {
@@ -99,6 +215,12 @@ impl IoSkip for StdinLock<'_> {
 		while consumed < bytes {
 			let buffered = self.fill_buf()?;
 			let buffered = buffered.len();
+			if buffered == 0 {
+				return Err(std::io::Error::new(
+					std::io::ErrorKind::UnexpectedEof,
+					"failed to skip past end of stream",
+				));
+			}
 			let consume = (bytes - consumed).min(buffered as u64);
 			self.consume(consume as _);
 			consumed += consume;
@@ -107,26 +229,6 @@ impl IoSkip for StdinLock<'_> {
 	}
 }
 
-#[cfg(windows)]
-pub fn ansi_to_wide(ansi: &[u8]) -> Result<Vec<u16>, std::io::Error> {
-	use winapi::um::{stringapiset::MultiByteToWideChar, winnls::CP_ACP};
-
-	// Get the required buffer size.
-	let required_size = unsafe { MultiByteToWideChar(CP_ACP, 0, ansi.as_ptr() as *const i8, ansi.len() as i32, core::ptr::null_mut(), 0) };
-	if required_size == 0 {
-		return Err(std::io::Error::last_os_error());
-	}
-
-	// Convert the ANSI string to wide string.
-	let mut wide = vec![0u16; required_size as usize];
-	let ret = unsafe { MultiByteToWideChar(CP_ACP, 0, ansi.as_ptr() as *const i8, ansi.len() as i32, wide.as_mut_ptr(), required_size) };
-	if ret == 0 {
-		return Err(std::io::Error::last_os_error());
-	}
-
-	Ok(wide)
-}
-
 #[cfg(feature = "binary")]
 mod binary {
 	use super::*;
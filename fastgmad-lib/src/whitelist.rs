@@ -1,156 +1,301 @@
 // https://github.com/garrynewman/bootil/blob/beb4cec8ad29533965491b767b177dc549e62d23/src/3rdParty/globber.cpp
 // https://github.com/Facepunch/gmad/blob/master/include/AddonWhiteList.h
 
-const ADDON_WHITELIST: &[&str] = &[
-	"lua/*.lua",
-	"scenes/*.vcd",
-	"particles/*.pcf",
-	"resource/fonts/*.ttf",
-	"scripts/vehicles/*.txt",
-	"resource/localization/*/*.properties",
-	"maps/*.bsp",
-	"maps/*.lmp",
-	"maps/*.nav",
-	"maps/*.ain",
-	"maps/thumb/*.png",
-	"sound/*.wav",
-	"sound/*.mp3",
-	"sound/*.ogg",
-	"materials/*.vmt",
-	"materials/*.vtf",
-	"materials/*.png",
-	"materials/*.jpg",
-	"materials/*.jpeg",
-	"materials/colorcorrection/*.raw",
-	"models/*.mdl",
-	"models/*.vtx",
-	"models/*.phy",
-	"models/*.ani",
-	"models/*.vvd",
-	"gamemodes/*/*.txt",
-	"gamemodes/*/*.fgd",
-	"gamemodes/*/logo.png",
-	"gamemodes/*/icon24.png",
-	"gamemodes/*/gamemode/*.lua",
-	"gamemodes/*/entities/effects/*.lua",
-	"gamemodes/*/entities/weapons/*.lua",
-	"gamemodes/*/entities/entities/*.lua",
-	"gamemodes/*/backgrounds/*.png",
-	"gamemodes/*/backgrounds/*.jpg",
-	"gamemodes/*/backgrounds/*.jpeg",
-	"gamemodes/*/content/models/*.mdl",
-	"gamemodes/*/content/models/*.vtx",
-	"gamemodes/*/content/models/*.phy",
-	"gamemodes/*/content/models/*.ani",
-	"gamemodes/*/content/models/*.vvd",
-	"gamemodes/*/content/materials/*.vmt",
-	"gamemodes/*/content/materials/*.vtf",
-	"gamemodes/*/content/materials/*.png",
-	"gamemodes/*/content/materials/*.jpg",
-	"gamemodes/*/content/materials/*.jpeg",
-	"gamemodes/*/content/materials/colorcorrection/*.raw",
-	"gamemodes/*/content/scenes/*.vcd",
-	"gamemodes/*/content/particles/*.pcf",
-	"gamemodes/*/content/resource/fonts/*.ttf",
-	"gamemodes/*/content/scripts/vehicles/*.txt",
-	"gamemodes/*/content/resource/localization/*/*.properties",
-	"gamemodes/*/content/maps/*.bsp",
-	"gamemodes/*/content/maps/*.nav",
-	"gamemodes/*/content/maps/*.ain",
-	"gamemodes/*/content/maps/thumb/*.png",
-	"gamemodes/*/content/sound/*.wav",
-	"gamemodes/*/content/sound/*.mp3",
-	"gamemodes/*/content/sound/*.ogg",
-	"data_static/*.txt",
-	"data_static/*.dat",
-	"data_static/*.json",
-	"data_static/*.xml",
-	"data_static/*.csv",
-	"data_static/*.dem",
-	"data_static/*.vcd",
-	"data_static/*.vtf",
-	"data_static/*.vmt",
-	"data_static/*.png",
-	"data_static/*.jpg",
-	"data_static/*.jpeg",
-	"data_static/*.mp3",
-	"data_static/*.wav",
-	"data_static/*.ogg",
-];
-
-const WILD_BYTE: u8 = b'*';
-const QUESTION_BYTE: u8 = b'?';
+use crate::error::{fastgmad_error, fastgmad_io_error, FastGmadError};
+use std::{
+	path::Path,
+	sync::{Arc, OnceLock},
+};
 
-fn globber(wild: &str, str: &str) -> bool {
-	unsafe {
-		let mut cp: *const u8 = core::ptr::null();
-		let mut mp: *const u8 = core::ptr::null();
+/// The built-in whitelist, which follows gmad's `AddonWhiteList.h`
+///
+/// Bump its version whenever it changes, so that `fastgmad whitelist` tells users which revision they have.
+const BUILTIN_WHITELIST: &str = include_str!("whitelist.json");
 
-		let (mut wild, wild_max) = (wild.as_ptr(), wild.as_ptr().add(wild.len()));
-		let (mut str, str_max) = (str.as_ptr(), str.as_ptr().add(str.len()));
+/// A set of globs that paths in a GMA must match
+///
+/// Globs are matched the way gmad does: `*` matches any characters including `/`, and `?` matches any one character.
+#[derive(Debug, Clone)]
+pub struct Whitelist {
+	versions: Vec<String>,
+	rules: Vec<WhitelistRule>,
 
-		while wild < wild_max && str < str_max && *wild != WILD_BYTE {
-			if *wild != *str && *wild != QUESTION_BYTE {
-				return false;
-			}
-			wild = wild.add(1);
-			str = str.add(1);
+	/// The rules, compiled on first use
+	globs: OnceLock<GlobSet>,
+
+	/// The folder part of each rule with a `*` on the end, compiled on first use, for finding rules for the folder a file is in
+	folder_globs: OnceLock<GlobSet>,
+}
+
+/// A glob in a [`Whitelist`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WhitelistRule {
+	/// The glob
+	pub glob: String,
+
+	/// Where the glob came from, e.g. `built-in gmad-1`
+	pub source: Arc<str>,
+}
+
+#[derive(serde::Deserialize)]
+struct WhitelistFile {
+	version: Option<String>,
+	whitelist: Vec<String>,
+}
+
+impl Whitelist {
+	/// The built-in whitelist
+	pub fn builtin() -> &'static Whitelist {
+		static BUILTIN: OnceLock<Whitelist> = OnceLock::new();
+		BUILTIN.get_or_init(|| Self::from_json(BUILTIN_WHITELIST, "built-in").expect("built-in whitelist is invalid"))
+	}
+
+	/// Parses a whitelist from JSON in the form `{ "version": "my-whitelist-1", "whitelist": ["data/*.txt"] }`
+	///
+	/// `source` describes where the JSON came from, and is combined with the version to label the rules. The version is optional.
+	pub fn from_json(json: &str, source: &str) -> Result<Self, FastGmadError> {
+		let file = serde_json::from_str::<WhitelistFile>(json).map_err(|error| fastgmad_error!(while "parsing whitelist", error: error))?;
+
+		let version = file.version.unwrap_or_else(|| "unversioned".to_owned());
+		let source = Arc::<str>::from(format!("{source} {version}"));
+
+		Ok(Self {
+			versions: vec![version],
+			rules: file
+				.whitelist
+				.into_iter()
+				.map(|glob| WhitelistRule {
+					glob,
+					source: source.clone(),
+				})
+				.collect(),
+			globs: OnceLock::new(),
+			folder_globs: OnceLock::new(),
+		})
+	}
+
+	/// Reads a whitelist file, see [`Whitelist::from_json`]
+	pub fn load(path: &Path) -> Result<Self, FastGmadError> {
+		let json = std::fs::read_to_string(path).map_err(|error| fastgmad_io_error!(while "reading whitelist", error: error, path: path))?;
+		Self::from_json(&json, &path.display().to_string())
+	}
+
+	/// The built-in whitelist, extended with the rules in whitelist files
+	pub fn with_files(paths: &[impl AsRef<Path>]) -> Result<Self, FastGmadError> {
+		let mut whitelist = Self::builtin().clone();
+		for path in paths {
+			whitelist.extend(Self::load(path.as_ref())?);
 		}
+		Ok(whitelist)
+	}
+
+	/// Adds the rules of another whitelist to this one
+	pub fn extend(&mut self, other: Whitelist) {
+		self.versions.extend(other.versions);
+		self.rules.extend(other.rules);
+		self.globs = OnceLock::new();
+		self.folder_globs = OnceLock::new();
+	}
+
+	/// The versions of the whitelists this was made from, joined with `+`
+	pub fn version(&self) -> String {
+		self.versions.join("+")
+	}
+
+	/// The rules in the whitelist
+	pub fn rules(&self) -> &[WhitelistRule] {
+		&self.rules
+	}
+
+	/// Check if a path is allowed in a GMA file
+	pub fn check(&self, path: &str) -> bool {
+		self.matching_rule(path).is_some()
+	}
 
-		while str < str_max {
-			if *wild == WILD_BYTE {
-				wild = wild.add(1);
-				if wild >= wild_max {
-					return true;
+	/// The first rule that allows a path, if any
+	pub fn matching_rule(&self, path: &str) -> Option<&WhitelistRule> {
+		let globs = self.globs.get_or_init(|| GlobSet::new(self.rules.iter().map(|rule| rule.glob.as_str())));
+		globs.first_match(path).map(|index| &self.rules[index])
+	}
+}
+
+/// gmad globs compiled into a single automaton, which finds every glob that matches a path in one pass
+///
+/// Like gmad, `*` matches any bytes including `/` and `?` matches any one byte.
+#[derive(Debug, Clone)]
+pub(crate) struct GlobSet(regex::bytes::RegexSet);
+impl GlobSet {
+	pub(crate) fn new<'a>(globs: impl IntoIterator<Item = &'a str>) -> Self {
+		let regexes = globs.into_iter().map(|glob| {
+			let mut regex = String::with_capacity(glob.len() * 2 + 8);
+			regex.push_str("(?s-u)^");
+			for c in glob.chars() {
+				match c {
+					'*' => regex.push_str(".*"),
+					'?' => regex.push('.'),
+					c => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
 				}
-				mp = wild;
-				cp = str.add(1);
-			} else if *wild == *str || *wild == QUESTION_BYTE {
-				wild = wild.add(1);
-				str = str.add(1);
-			} else {
-				wild = mp;
-				str = cp;
-				cp = cp.add(1);
 			}
-		}
+			regex.push('$');
+			regex
+		});
 
-		while wild < wild_max && *wild == WILD_BYTE {
-			wild = wild.add(1);
-		}
+		// Every glob translates to a valid regex, so this can only fail if the set is too big to compile
+		Self(
+			regex::bytes::RegexSetBuilder::new(regexes)
+				.size_limit(usize::MAX)
+				.dfa_size_limit(64 * 1024 * 1024)
+				.build()
+				.expect("failed to compile globs"),
+		)
+	}
 
-		wild >= wild_max
+	pub(crate) fn is_empty(&self) -> bool {
+		self.0.is_empty()
+	}
+
+	pub(crate) fn is_match(&self, path: &str) -> bool {
+		self.0.is_match(path.as_bytes())
+	}
+
+	/// The index of the first glob that matches the path
+	pub(crate) fn first_match(&self, path: &str) -> Option<usize> {
+		self.0.matches(path.as_bytes()).iter().next()
+	}
+
+	/// The indices of every glob that matches the path
+	pub(crate) fn matches(&self, path: &str) -> impl Iterator<Item = usize> {
+		self.0.matches(path.as_bytes()).into_iter()
 	}
 }
 
-/// Check if a path is allowed in a GMA file
+/// Check if a path is allowed in a GMA file by the built-in whitelist
 pub fn check(str: &str) -> bool {
-	for glob in ADDON_WHITELIST {
-		if globber(glob, str) {
-			return true;
+	Whitelist::builtin().check(str)
+}
+
+/// A file that isn't allowed in a GMA, with a suggestion for how to fix it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WhitelistViolation {
+	/// The path of the file in the GMA
+	pub path: String,
+
+	/// A human-readable suggestion, if one could be found
+	pub suggestion: Option<String>,
+}
+impl WhitelistViolation {
+	/// Creates a violation for a path, looking for a suggestion in a whitelist
+	pub fn new(path: String, whitelist: &Whitelist) -> Self {
+		let suggestion = whitelist.suggest(&path);
+		Self { path, suggestion }
+	}
+}
+impl std::fmt::Display for WhitelistViolation {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match &self.suggestion {
+			Some(suggestion) => write!(f, "{} ({})", self.path, suggestion),
+			None => f.write_str(&self.path),
+		}
+	}
+}
+
+impl Whitelist {
+	/// Suggests how to fix a path that isn't allowed in a GMA
+	pub fn suggest(&self, path: &str) -> Option<String> {
+		// The addon is nested inside another folder, e.g. `my_addon/lua/autorun/init.lua`
+		let mut rest = path;
+		while let Some((_, next)) = rest.split_once('/') {
+			if self.check(next) {
+				return Some(format!("did you mean {next}? Only the folders inside the addon folder are packed"));
+			}
+			rest = next;
+		}
+
+		let lowercase = path.to_lowercase();
+		if lowercase != path && self.check(&lowercase) {
+			return Some(format!("did you mean {lowercase}? Use -lowercase to convert all paths to lower case"));
+		}
+
+		// A misspelled folder, e.g. `sounds/` instead of `sound/`
+		let (dir, rest) = path.split_once('/')?;
+		let mut best: Option<(usize, &str)> = None;
+		for whitelisted_dir in self.rules.iter().filter_map(|rule| rule.glob.split_once('/')).map(|(dir, _)| dir) {
+			let distance = edit_distance(&dir.to_lowercase(), whitelisted_dir);
+			if distance <= 2 && best.is_none_or(|(best, _)| distance < best) && self.check(&format!("{whitelisted_dir}/{rest}")) {
+				best = Some((distance, whitelisted_dir));
+			}
+		}
+		if let Some((_, whitelisted_dir)) = best {
+			return Some(format!("did you mean {whitelisted_dir}/{rest}?"));
+		}
+
+		// The folder is right but the file type isn't, so list what's allowed there
+		let folder_globs = self.folder_globs.get_or_init(|| {
+			GlobSet::new(self.rules.iter().map(|rule| match rule.glob.rfind('/') {
+				// Ends with `/*`
+				Some(slash) => &rule.glob[..slash + 2],
+				// Never matches a path
+				None => "",
+			}))
+		});
+		let mut longest_prefix = 0;
+		let mut nearest = Vec::new();
+		for index in folder_globs.matches(path) {
+			let glob = self.rules[index].glob.as_str();
+			let prefix = glob.rfind('/').map(|slash| slash + 1).unwrap_or_default();
+			if prefix < longest_prefix {
+				continue;
+			}
+			if prefix > longest_prefix {
+				longest_prefix = prefix;
+				nearest.clear();
+			}
+			nearest.push(glob);
+		}
+		match nearest.as_slice() {
+			[] => None,
+			[glob] => Some(format!("files here must match {glob}")),
+			[globs @ .., last] => Some(format!("files here must match {} or {}", globs.join(", "), last)),
 		}
 	}
+}
 
-	false
+/// Suggests how to fix a path that isn't allowed in a GMA by the built-in whitelist
+pub fn suggest(path: &str) -> Option<String> {
+	Whitelist::builtin().suggest(path)
 }
 
-/// Check if a path is ignored by a list of custom globs
+/// Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+	let b = b.chars().collect::<Vec<_>>();
+	let mut row = (0..=b.len()).collect::<Vec<_>>();
+	for (i, a) in a.chars().enumerate() {
+		let mut diagonal = row[0];
+		row[0] = i + 1;
+		for (j, b) in b.iter().enumerate() {
+			let substitution = diagonal + usize::from(a != *b);
+			diagonal = row[j + 1];
+			row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+		}
+	}
+	row[b.len()]
+}
+
+/// Check if a path is ignored by a list of custom globs, matched the way gmad does
+///
+/// This is how fastgmad matches addon.json's `ignore` list by default, see [`crate::ignore::IgnoreSyntax`].
+///
+/// This compiles the globs every time it's called; [`crate::ignore::IgnoreList`] compiles them once.
 pub fn is_ignored(str: &str, ignore: &[String]) -> bool {
 	if ignore.is_empty() {
 		return false;
 	}
 
-	for glob in ignore {
-		if globber(glob, str) {
-			return true;
-		}
-	}
-
-	false
+	GlobSet::new(ignore.iter().map(String::as_str)).is_match(str)
 }
 
 #[test]
-pub fn test_whitelist() {
+fn test_whitelist() {
 	let good: &[&str] = &[
 		"lua/test.lua",
 		"lua/lol/test.lua",
@@ -177,12 +322,12 @@ pub fn test_whitelist() {
 		assert!(check(&*good), "{}", good);
 	}
 
-	for good in ADDON_WHITELIST {
-		assert!(check(&good.replace('*', "test")));
+	for good in Whitelist::builtin().rules() {
+		assert!(check(&good.glob.replace('*', "test")));
 	}
 
-	for good in ADDON_WHITELIST {
-		assert!(check(&good.replace('*', "a")));
+	for good in Whitelist::builtin().rules() {
+		assert!(check(&good.glob.replace('*', "a")));
 	}
 
 	for bad in bad {
@@ -191,10 +336,157 @@ pub fn test_whitelist() {
 }
 
 #[test]
-pub fn test_ignore() {
+fn test_ignore() {
 	assert!(is_ignored(&"lol.txt".to_string(), &["lol.txt".to_string()]));
 	assert!(is_ignored(&"lua/hello.lua".to_string(), &["lua/*.lua".to_string()]));
 	assert!(is_ignored(&"lua/hello.lua".to_string(), &["lua/*".to_string()]));
 	assert!(is_ignored(&".gitattributes".to_string(), &[".git*".to_string()]));
 	assert!(!is_ignored(&"lol.txt".to_string(), &[]));
 }
+
+#[test]
+fn test_whitelist_compiled_and_backup_lua() {
+	assert!(!check("lua/test.luac"));
+	assert!(!check("lua/test.lua.bak"));
+}
+
+#[test]
+fn test_ignore_whole_names() {
+	assert!(!is_ignored("lol.txt.bak", &["lol.txt".to_string()]));
+	assert!(!is_ignored("lol", &["lol.txt".to_string()]));
+}
+
+#[test]
+fn test_suggest() {
+	assert_eq!(suggest("sounds/test.wav").unwrap(), "did you mean sound/test.wav?");
+	assert_eq!(suggest("material/test.vmt").unwrap(), "did you mean materials/test.vmt?");
+	assert_eq!(
+		suggest("Models/test.mdl").unwrap(),
+		"did you mean models/test.mdl? Use -lowercase to convert all paths to lower case"
+	);
+	assert_eq!(
+		suggest("my_addon/lua/autorun/init.lua").unwrap(),
+		"did you mean lua/autorun/init.lua? Only the folders inside the addon folder are packed"
+	);
+	assert_eq!(
+		suggest("sound/test.flac").unwrap(),
+		"files here must match sound/*.wav, sound/*.mp3 or sound/*.ogg"
+	);
+	assert_eq!(suggest("lua/test.exe").unwrap(), "files here must match lua/*.lua");
+	assert_eq!(suggest("README.md"), None);
+	assert_eq!(suggest("src/test.lua"), None);
+
+	assert_eq!(edit_distance("sounds", "sound"), 1);
+	assert_eq!(edit_distance("kitten", "sitting"), 3);
+	assert_eq!(edit_distance("", "abc"), 3);
+}
+
+#[test]
+fn test_whitelist_extensions() {
+	let mut whitelist = Whitelist::builtin().clone();
+	assert!(!whitelist.check("data/test.txt"));
+
+	whitelist.extend(Whitelist::from_json(r#"{ "version": "private-1", "whitelist": ["data/*.txt"] }"#, "test").unwrap());
+	assert!(whitelist.check("data/test.txt"));
+	assert!(whitelist.check("lua/test.lua"));
+	assert_eq!(whitelist.version(), "gmad-1+private-1");
+	assert_eq!(&*whitelist.matching_rule("data/test.txt").unwrap().source, "test private-1");
+	assert_eq!(whitelist.matching_rule("lua/test.lua").unwrap().glob, "lua/*.lua");
+	assert_eq!(&*whitelist.matching_rule("lua/test.lua").unwrap().source, "built-in gmad-1");
+
+	assert!(Whitelist::from_json(r#"{ "whitelist": "data/*.txt" }"#, "test").is_err());
+}
+
+/// bootil's globber, which gmad uses, to check the compiled globs against
+#[cfg(test)]
+fn globber(wild: &str, str: &str) -> bool {
+	const WILD_BYTE: u8 = b'*';
+	const QUESTION_BYTE: u8 = b'?';
+
+	let (wild, str) = (wild.as_bytes(), str.as_bytes());
+
+	// The original reads the NUL terminator at the end of the glob, so we do the same rather than reading out of bounds
+	let at = |i: usize| wild.get(i).copied().unwrap_or(0);
+
+	let (mut w, mut s) = (0, 0);
+	while s < str.len() && at(w) != WILD_BYTE {
+		if at(w) != str[s] && at(w) != QUESTION_BYTE {
+			return false;
+		}
+		w += 1;
+		s += 1;
+	}
+
+	let mut mp = None;
+	let mut cp = 0;
+	while s < str.len() {
+		if at(w) == WILD_BYTE {
+			w += 1;
+			if w >= wild.len() {
+				return true;
+			}
+			mp = Some(w);
+			cp = s + 1;
+		} else if w < wild.len() && (wild[w] == str[s] || wild[w] == QUESTION_BYTE) {
+			w += 1;
+			s += 1;
+		} else {
+			match mp {
+				Some(mp) => w = mp,
+				None => return false,
+			}
+			s = cp;
+			cp += 1;
+		}
+	}
+
+	while at(w) == WILD_BYTE {
+		w += 1;
+	}
+
+	w >= wild.len()
+}
+
+#[test]
+fn test_glob_set_agrees_with_globber() {
+	let globs = Whitelist::builtin()
+		.rules()
+		.iter()
+		.map(|rule| rule.glob.as_str())
+		.chain(["lol.txt", "*.psd", "a?c", "*/x/*", "**", "lua/*/*.lua", "é*", "?"])
+		.collect::<Vec<_>>();
+	let set = GlobSet::new(globs.iter().copied());
+
+	let paths = [
+		"",
+		"a",
+		"abc",
+		"abcd",
+		"lol.txt",
+		"lol.txt.bak",
+		"x.psd",
+		"a/b/c.psd",
+		"lua/x.lua",
+		"lua/a/b.lua",
+		"lua/.lua",
+		"lua/x.luac",
+		"a/x/b",
+		"é",
+		"éa",
+		"ab",
+		"gamemodes/x/content/sound/a.wav",
+		"gamemodes/x/y/content/sound/a.wav",
+		"gamemodes//logo.png",
+		"data_static/a.b.c.csv",
+	];
+	for path in paths {
+		let expected = globs
+			.iter()
+			.enumerate()
+			.filter(|(_, glob)| globber(glob, path))
+			.map(|(index, _)| index)
+			.collect::<Vec<_>>();
+		assert_eq!(set.matches(path).collect::<Vec<_>>(), expected, "{path}");
+		assert_eq!(set.first_match(path), expected.first().copied(), "{path}");
+	}
+}
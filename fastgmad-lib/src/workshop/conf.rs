@@ -165,7 +165,10 @@ mod binary {
 	fn consume_create_args(addon: &Path, mut create_args: Vec<OsString>) -> Result<Option<CreateGmaConfig>, PrintHelp> {
 		if addon.is_dir() {
 			// Don't let the user provide any output arguments
-			if create_args.iter().any(|arg| matches!(arg.to_str(), Some("-out") | Some("-stdout"))) {
+			if create_args
+				.iter()
+				.any(|arg| matches!(arg.to_str(), Some("-out") | Some("-stdout") | Some("-folder")))
+			{
 				return Err(PrintHelp(Some("Unknown publishing argument")));
 			}
 
@@ -29,72 +29,6 @@ use std::{
 };
 use uuid::Uuid;
 
-#[cfg(feature = "binary")]
-mod ctrlc_handling {
-	// cargo build --package fastgmad-publish --features binary && cargo run --package fastgmad-bin -- publish -addon C:\Users\William\Documents\GitHub\fastgmad\fastgmad-lib\test_data\wiremod.gma
-
-	struct CtrlCState {
-		handles: usize,
-		pressed: bool,
-	}
-
-	static CTRL_C_INSTALLED: std::sync::Once = std::sync::Once::new();
-	static CTRL_C_STATE: std::sync::Mutex<CtrlCState> = std::sync::Mutex::new(CtrlCState { handles: 0, pressed: false });
-
-	fn exit_ctrlc() {
-		std::process::exit(if cfg!(windows) {
-			-1073741510
-		} else if cfg!(unix) {
-			130
-		} else {
-			1
-		});
-	}
-
-	pub struct CtrlCHandle;
-	impl CtrlCHandle {
-		pub fn get() -> Self {
-			let mut state = CTRL_C_STATE.lock().unwrap();
-
-			state.handles += 1;
-
-			CTRL_C_INSTALLED.call_once(|| {
-				ctrlc::set_handler(|| {
-					let mut state = CTRL_C_STATE.lock().unwrap();
-					if state.handles == 0 {
-						exit_ctrlc();
-					} else {
-						if core::mem::replace(&mut state.pressed, true) {
-							// Already pressed, exit immediately
-							exit_ctrlc();
-						}
-						eprintln!();
-						log::warn!("Aborting, please wait...");
-					}
-				})
-				.ok();
-			});
-
-			drop(state);
-
-			CtrlCHandle
-		}
-
-		pub fn check(&self, cleanup: impl FnOnce()) {
-			if CTRL_C_STATE.lock().unwrap().pressed {
-				cleanup();
-				exit_ctrlc();
-				log::warn!("Aborted by user");
-			}
-		}
-	}
-	impl Drop for CtrlCHandle {
-		fn drop(&mut self) {
-			CTRL_C_STATE.lock().unwrap().handles -= 1;
-		}
-	}
-}
-
 const LEGAL_AGREEMENT_MESSAGE: &str = r#"
 You must accept the Steam Workshop legal agreement before you can make your addon public.
 You can do this at https://steamcommunity.com/sharedfiles/workshoplegalagreement
@@ -192,7 +126,7 @@ fn workshop_upload(
 	let mut metadata = GmaPublishingMetadata::try_read(addon.as_ref())?;
 
 	#[cfg(feature = "binary")]
-	let ctrlc_handle = ctrlc_handling::CtrlCHandle::get();
+	let ctrlc_handle = crate::ctrlc_handling::CtrlCHandle::get();
 
 	let file_id;
 	let mut legal_agreement_pending;